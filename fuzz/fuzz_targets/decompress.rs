@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use lz4::{Buf, HeapBuf};
+use std::io;
+
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = lz4::frame_info(data);
+    let _ = lz4::decompress_frames(data, &mut HeapBuf::new());
+    let _ = lz4::decompress_legacy(data, &mut HeapBuf::new());
+    let _ = io::copy(&mut lz4::FrameDecoder::new(data), &mut io::sink());
+
+    // the push decoder must agree with `decompress`, no matter how the input is split
+    let mut out = HeapBuf::new();
+    let expected = lz4::decompress(data, &mut out).map(|()| out.as_slice().to_vec());
+    assert_eq!(expected.map_err(|err| err.kind), common::decompress_pushed(data, 5));
+});
@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use lz4::{ArrayBuf, HeapBuf};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = lz4::decompress_block(data, &mut HeapBuf::new());
+    let _ = lz4::decompress_block(data, &mut ArrayBuf::<u8, 256>::new());
+
+    // use the first bytes as the dictionary
+    if let Some((&len, data)) = data.split_first() {
+        let (dict, block) = data.split_at((len as usize).min(data.len()));
+        let _ = lz4::decompress_block_with_dict(block, dict, &mut HeapBuf::new());
+    }
+});
@@ -22,6 +22,11 @@ pub trait Buf<T: Copy> {
     /// Return the number of initialized elements in this buffer.
     fn len(&self) -> usize;
 
+    /// Returns `true` if this buffer doesn't contain any elements.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Returns a slice to the inner storage of this buf.
     fn as_slice(&self) -> &[T];
 
@@ -34,6 +39,17 @@ pub trait Buf<T: Copy> {
     /// and `false` if there's not enough memory left.
     fn extend(&mut self, buf: &[T]) -> bool;
 
+    /// Shortens this buffer, keeping the first `len` elements.
+    ///
+    /// If `len` is greater than this buffers len, this has no effect.
+    ///
+    /// Returns `true` if this buffer contains at most `len` elements afterwards,
+    /// and `false` if it can't be shortened. The default implementation can't shorten
+    /// the buffer, so it only succeeds if there's nothing to remove.
+    fn truncate(&mut self, len: usize) -> bool {
+        len >= self.len()
+    }
+
     /// Resizes this buffer so that the new length is equal to `len`.
     ///
     /// If this buffers len is greater than the given len, the required elements
@@ -56,7 +72,7 @@ pub trait Buf<T: Copy> {
             return false;
         }
 
-        (0..diff).map(|_| item.clone()).for_each(|item| {
+        (0..diff).for_each(|_| {
             self.push(item);
         });
         true
@@ -84,6 +100,12 @@ impl<T, const N: usize> ArrayBuf<T, N> {
     }
 }
 
+impl<T: Default + Copy, const N: usize> Default for ArrayBuf<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Copy, const N: usize> Buf<T> for ArrayBuf<T, N> {
     fn push(&mut self, item: T) -> Option<T> {
         let entry = match self.arr.get_mut(self.len) {
@@ -107,7 +129,12 @@ impl<T: Copy, const N: usize> Buf<T> for ArrayBuf<T, N> {
     }
 
     fn reserve(&mut self, count: usize) -> bool {
-        self.len + count <= N
+        matches!(self.len.checked_add(count), Some(len) if len <= N)
+    }
+
+    fn truncate(&mut self, len: usize) -> bool {
+        self.len = self.len.min(len);
+        true
     }
 
     fn len(&self) -> usize {
@@ -149,6 +176,12 @@ mod heap {
         }
     }
 
+    impl<T> Default for HeapBuf<T> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl<T: Copy> Buf<T> for HeapBuf<T> {
         fn push(&mut self, item: T) -> Option<T> {
             self.0.push(item);
@@ -166,6 +199,11 @@ mod heap {
             true
         }
 
+        fn truncate(&mut self, len: usize) -> bool {
+            self.0.truncate(len);
+            true
+        }
+
         fn len(&self) -> usize {
             self.0.len()
         }
@@ -1,4 +1,75 @@
 //! Implementation of the compression algorithm.
 
+use core::fmt;
+
+mod dict;
+pub use dict::*;
+
+mod framed;
+pub use framed::*;
+
+mod hc;
+pub use hc::*;
+
+mod legacy;
+pub use legacy::*;
+
+mod opt;
+
+mod push;
+pub use push::*;
+
 mod raw;
 pub use raw::*;
+
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+pub use stream::*;
+
+/// The error type that is returned by various compression-related methods.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum CompressError {
+    /// Inidicates that the `out` pointer didn't contain enough memory
+    /// to store the compressed result.
+    MemoryLimitExceeded,
+    /// The input is larger than the maximum size LZ4 is able to compress.
+    ///
+    /// See [`compressed_bound`](crate::compressed_bound) for the exact limit.
+    InputTooLarge,
+    /// The index of a skippable frame is larger than 15.
+    InvalidSkippableIndex,
+    /// A frame is compressed with a dictionary, but the preferences don't contain
+    /// its [dictionary ID](FramePreferences::dictionary_id).
+    MissingDictionaryId,
+    /// The `out` pointer couldn't remove partially written output,
+    /// because it doesn't support [`Buf::truncate`](crate::Buf::truncate).
+    ///
+    /// Unlike [`MemoryLimitExceeded`](Self::MemoryLimitExceeded), the `out` pointer
+    /// contains incomplete data afterwards, which has to be discarded.
+    TruncateFailed,
+}
+
+impl fmt::Display for CompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressError::MemoryLimitExceeded => {
+                f.write_str("Not enough memory available in out pointer.")
+            }
+            CompressError::InputTooLarge => f.write_str("The input is too large to be compressed."),
+            CompressError::InvalidSkippableIndex => {
+                f.write_str("The index of a skippable frame must be smaller than 16.")
+            }
+            CompressError::MissingDictionaryId => {
+                f.write_str("The dictionary ID of a frame with a dictionary is missing.")
+            }
+            CompressError::TruncateFailed => {
+                f.write_str("The out pointer couldn't remove partially written output.")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CompressError {}
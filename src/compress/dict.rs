@@ -0,0 +1,139 @@
+//! Dictionaries that are prepared once and then used for many compressions.
+
+use super::raw::{compress_with_table, index, Window, HASH_SIZE, MAX_DISTANCE};
+use super::CompressError;
+use crate::Buf;
+
+/// A dictionary whose content is already inserted into the match table
+/// of the fast compressor.
+///
+/// Compressing with a dictionary requires hashing the whole dictionary first,
+/// which takes up most of the time if the input is small. A `PreparedDictionary`
+/// does this only once and can then be used by many compressions, which
+/// only have to copy the prepared table.
+///
+/// The dictionary is immutable and can be shared between threads.
+/// Only the last 64KB of the dictionary are used.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{Buf, HeapBuf, PreparedDictionary};
+///
+/// let dict = PreparedDictionary::new(br#"{"id":0,"name":"","active":false}"#);
+///
+/// for msg in &[&br#"{"id":1,"name":"lz4","active":true}"#[..], br#"{"id":2}"#] {
+///     let mut out = HeapBuf::new();
+///     lz4::compress_block_with_prepared_dict(msg, &dict, &mut out).unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct PreparedDictionary<'dict> {
+    dict: &'dict [u8],
+    table: [u32; HASH_SIZE],
+}
+
+impl<'dict> PreparedDictionary<'dict> {
+    /// Prepares the given dictionary.
+    pub fn new(dict: &'dict [u8]) -> Self {
+        let dict = &dict[dict.len().saturating_sub(MAX_DISTANCE)..];
+        let mut table = [0; HASH_SIZE];
+        index(Window::new(dict, &[]), dict.len(), &mut table);
+        Self { dict, table }
+    }
+
+    /// Returns the part of the dictionary that is used for compression.
+    ///
+    /// This must be used to decompress the data again.
+    pub fn as_bytes(&self) -> &'dict [u8] {
+        self.dict
+    }
+
+    /// Compresses `input` into a single LZ4 block, using a copy of the prepared table.
+    pub(crate) fn compress<O: Buf<u8>>(
+        &self,
+        input: &[u8],
+        out: &mut O,
+        acceleration: u32,
+        max_distance: u16,
+    ) -> Result<(), CompressError> {
+        let mut table = self.table;
+        let window = Window::new(self.dict, input).with_max_distance(max_distance);
+        compress_with_table(window, self.dict.len(), &mut table, out, acceleration)
+    }
+}
+
+/// Compresses `input` into a single LZ4 block using a prepared dictionary.
+///
+/// The output is exactly the same as the output of
+/// [`compress_block_with_dict`](crate::compress::compress_block_with_dict),
+/// but the dictionary doesn't have to be hashed again.
+pub fn compress_block_with_prepared_dict<O: Buf<u8>>(
+    input: &[u8],
+    dict: &PreparedDictionary<'_>,
+    out: &mut O,
+) -> Result<(), CompressError> {
+    dict.compress(input, out, 1, u16::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreparedDictionary;
+    use crate::{compress_block_with_dict, decompress_block_with_dict, Buf, HeapBuf};
+    use alloc::sync::Arc;
+
+    #[test]
+    fn prepared_dict_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PreparedDictionary<'static>>();
+    }
+
+    #[test]
+    fn prepared_dict() {
+        let data = crate::test_data(28 * 1024, 1, &[100, 3000]);
+        let (raw, rest) = data.split_at(16 * 1024);
+        let dict = PreparedDictionary::new(raw);
+        assert_eq!(dict.as_bytes(), raw);
+
+        for input in rest.chunks(500) {
+            let mut prepared = HeapBuf::new();
+            super::compress_block_with_prepared_dict(input, &dict, &mut prepared).unwrap();
+
+            let mut plain = HeapBuf::new();
+            compress_block_with_dict(input, raw, &mut plain).unwrap();
+            assert_eq!(prepared.as_slice(), plain.as_slice());
+
+            let mut decompressed = HeapBuf::new();
+            decompress_block_with_dict(prepared.as_slice(), dict.as_bytes(), &mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed.as_slice(), input);
+        }
+    }
+
+    #[test]
+    fn prepared_dict_threads() {
+        // the threads require the data to live forever
+        let data: &'static [u8] =
+            Box::leak(crate::test_data(17 * 1024, 1, &[100, 3000]).into_boxed_slice());
+        let (dict, input) = data.split_at(16 * 1024);
+        let dict = Arc::new(PreparedDictionary::new(dict));
+
+        let threads = (0..4)
+            .map(|_| {
+                let dict = Arc::clone(&dict);
+                std::thread::spawn(move || {
+                    let mut out = HeapBuf::new();
+                    super::compress_block_with_prepared_dict(input, &dict, &mut out).unwrap();
+                    out
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            let out = thread.join().unwrap();
+            let mut decompressed = HeapBuf::new();
+            decompress_block_with_dict(out.as_slice(), dict.as_bytes(), &mut decompressed).unwrap();
+            assert_eq!(decompressed.as_slice(), input);
+        }
+    }
+}
@@ -0,0 +1,691 @@
+//! Compression into the LZ4 [Frame Format]
+//!
+//! [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+
+use super::raw::Window;
+use super::{CompressError, CompressionLevel, PreparedDictionary};
+use crate::decompress::{Flags, MAGIC, SKIPPABLE_MAGIC, UNCOMPRESSED_DATA, VERSION};
+use crate::Buf;
+use core::hash::Hasher;
+use twox_hash::XxHash32;
+
+/// Linked blocks can reference up to 64KB of data from the previous blocks.
+pub(super) const WINDOW_SIZE: usize = 64 * 1024;
+
+/// The maximum size of a single block inside a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockSize {
+    /// Blocks are at most 64KB large.
+    Max64KB,
+    /// Blocks are at most 256KB large.
+    Max256KB,
+    /// Blocks are at most 1MB large.
+    Max1MB,
+    /// Blocks are at most 4MB large.
+    Max4MB,
+}
+
+impl BlockSize {
+    /// Returns the maximum number of bytes a block can contain.
+    pub const fn size(self) -> usize {
+        1 << (self.id() * 2 + 8)
+    }
+
+    /// Returns the block size that is represented by `id` inside the block descriptor.
+    pub(crate) const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            4 => Some(BlockSize::Max64KB),
+            5 => Some(BlockSize::Max256KB),
+            6 => Some(BlockSize::Max1MB),
+            7 => Some(BlockSize::Max4MB),
+            _ => None,
+        }
+    }
+
+    /// Returns the value that represents this block size inside the block descriptor.
+    pub(crate) const fn id(self) -> u8 {
+        match self {
+            BlockSize::Max64KB => 4,
+            BlockSize::Max256KB => 5,
+            BlockSize::Max1MB => 6,
+            BlockSize::Max4MB => 7,
+        }
+    }
+}
+
+/// Preferences that control how a frame is written.
+///
+/// The default preferences produce a frame with independent blocks of at most 64KB,
+/// and only a checksum of the whole content, using the fast compressor.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{BlockSize, CompressionLevel, FramePreferences};
+///
+/// let prefs = FramePreferences::new()
+///     .compression_level(CompressionLevel::MAX)
+///     .block_size(BlockSize::Max4MB)
+///     .content_checksum(false);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramePreferences {
+    level: Option<CompressionLevel>,
+    acceleration: u32,
+    pub(super) block_size: BlockSize,
+    pub(super) independent_blocks: bool,
+    block_checksums: bool,
+    pub(super) content_checksum: bool,
+    pub(super) content_size: bool,
+    dictionary_id: Option<u32>,
+    max_distance: u16,
+}
+
+impl FramePreferences {
+    /// Create the default preferences.
+    pub const fn new() -> Self {
+        Self {
+            level: None,
+            acceleration: 1,
+            block_size: BlockSize::Max64KB,
+            independent_blocks: true,
+            block_checksums: false,
+            content_checksum: true,
+            content_size: false,
+            dictionary_id: None,
+            max_distance: u16::MAX,
+        }
+    }
+
+    /// Compresses the blocks using the high compression mode with the given level.
+    ///
+    /// By default, the fast compressor is used. Without the `alloc` feature,
+    /// the high compression mode needs about 256KB of stack,
+    /// see [`compress_block_hc`](crate::compress_block_hc).
+    pub const fn compression_level(mut self, level: CompressionLevel) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Sets the acceleration of the fast compressor.
+    ///
+    /// Higher values make the compression faster, but result in a worse compression ratio.
+    /// This has no effect if a compression level is set.
+    /// See [`compress_block_fast`](crate::compress::compress_block_fast) for more information.
+    pub const fn acceleration(mut self, acceleration: u32) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    /// Sets the maximum size of a single block.
+    pub const fn block_size(mut self, size: BlockSize) -> Self {
+        self.block_size = size;
+        self
+    }
+
+    /// Sets if every block is compressed on its own.
+    ///
+    /// Linked blocks can reference data from the previous blocks, which improves
+    /// the compression ratio, but requires the decompressor to keep the last 64KB
+    /// of output around.
+    pub const fn independent_blocks(mut self, enabled: bool) -> Self {
+        self.independent_blocks = enabled;
+        self
+    }
+
+    /// Sets if every block is followed by a checksum of its data.
+    pub const fn block_checksums(mut self, enabled: bool) -> Self {
+        self.block_checksums = enabled;
+        self
+    }
+
+    /// Sets if the frame ends with a checksum of the uncompressed content.
+    pub const fn content_checksum(mut self, enabled: bool) -> Self {
+        self.content_checksum = enabled;
+        self
+    }
+
+    /// Sets if the size of the uncompressed content is stored in the frame header.
+    pub const fn content_size(mut self, enabled: bool) -> Self {
+        self.content_size = enabled;
+        self
+    }
+
+    /// Sets the ID of the dictionary, that is used to compress the frame.
+    ///
+    /// The ID is stored in the frame header, so the decompressor is able to
+    /// find the right dictionary. See [`compress_with_dict`] for more information.
+    pub const fn dictionary_id(mut self, id: u32) -> Self {
+        self.dictionary_id = Some(id);
+        self
+    }
+
+    /// Limits the offset of all matches to `distance` bytes.
+    ///
+    /// This allows decoders, that keep less than the usual 64KB of previous output around,
+    /// to decompress the frame. Matches of linked blocks are also limited, so such a decoder
+    /// only has to keep the last `distance` bytes of the previous blocks.
+    /// See [`compress_block_with_max_distance`](crate::compress::compress_block_with_max_distance)
+    /// for more information.
+    pub const fn max_distance(mut self, distance: u16) -> Self {
+        self.max_distance = distance;
+        self
+    }
+
+    pub(crate) fn flags(&self) -> Flags {
+        let mut flags = Flags::empty();
+        flags.set(Flags::IndependentBlocks, self.independent_blocks);
+        flags.set(Flags::BlockChecksums, self.block_checksums);
+        flags.set(Flags::ContentChecksum, self.content_checksum);
+        flags.set(Flags::ContentSize, self.content_size);
+        flags.set(Flags::DictionaryId, self.dictionary_id.is_some());
+        flags
+    }
+}
+
+impl Default for FramePreferences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compresses `input` using the LZ4 [Frame Format].
+///
+/// The resulting data can be decompressed by [`decompress`](crate::decompress::decompress)
+/// or by any other tool that understands the frame format, like the reference `lz4` tool.
+/// The frame is written using the default [`FramePreferences`], use
+/// [`compress_with`] to customize them.
+///
+/// If `out` runs out of memory, [`CompressError::MemoryLimitExceeded`] is returned.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn compress<B: Buf<u8>>(input: &[u8], out: &mut B) -> Result<(), CompressError> {
+    compress_with(input, out, &FramePreferences::new())
+}
+
+/// Compresses `input` using the LZ4 [Frame Format] and the given preferences.
+///
+/// See [`compress`] for more information.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn compress_with<B: Buf<u8>>(
+    input: &[u8],
+    out: &mut B,
+    prefs: &FramePreferences,
+) -> Result<(), CompressError> {
+    compress_frame(input, &[], None, out, prefs)
+}
+
+/// Compresses `input` using the LZ4 [Frame Format], while allowing
+/// the blocks to reference the content of `dict`.
+///
+/// The frame can only be decompressed using the same dictionary. The preferences have to
+/// contain its [dictionary ID](FramePreferences::dictionary_id), which the decompressor
+/// uses to look up the dictionary in
+/// [`decompress_with_dictionaries`](crate::decompress::decompress_with_dictionaries).
+/// Otherwise, [`CompressError::MissingDictionaryId`] is returned.
+///
+/// See [`compress`] for more information.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn compress_with_dict<B: Buf<u8>>(
+    input: &[u8],
+    dict: &[u8],
+    out: &mut B,
+    prefs: &FramePreferences,
+) -> Result<(), CompressError> {
+    if prefs.dictionary_id.is_none() {
+        return Err(CompressError::MissingDictionaryId);
+    }
+    compress_frame(input, dict, None, out, prefs)
+}
+
+/// Compresses `input` using the LZ4 [Frame Format] and a prepared dictionary.
+///
+/// The prepared dictionary is only used by the fast compressor, if a
+/// [compression level](FramePreferences::compression_level) is set, the dictionary
+/// is hashed again. See [`compress_with_dict`] for more information.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn compress_with_prepared_dict<B: Buf<u8>>(
+    input: &[u8],
+    dict: &PreparedDictionary<'_>,
+    out: &mut B,
+    prefs: &FramePreferences,
+) -> Result<(), CompressError> {
+    if prefs.dictionary_id.is_none() {
+        return Err(CompressError::MissingDictionaryId);
+    }
+    compress_frame(input, dict.as_bytes(), Some(dict), out, prefs)
+}
+
+/// Writes a skippable frame, which contains `data` instead of compressed data.
+///
+/// Skippable frames can be put in front of or behind other frames, to embed metadata
+/// that is ignored by decompressors. The `index` selects one of the 16 magic numbers
+/// of skippable frames, which allows to distinguish between different kinds of metadata.
+/// Use [`decompress_with_skippable`](crate::decompress::decompress_with_skippable)
+/// to read the data again.
+///
+/// If `data` is larger than 4GB, [`CompressError::InputTooLarge`] is returned, and if
+/// `index` is larger than 15, [`CompressError::InvalidSkippableIndex`] is returned.
+/// If `out` runs out of memory, [`CompressError::MemoryLimitExceeded`] is returned
+/// and nothing is written, unless `out` can't be shortened again, in which case
+/// [`CompressError::TruncateFailed`] is returned.
+pub fn write_skippable_frame<B: Buf<u8>>(
+    index: u8,
+    data: &[u8],
+    out: &mut B,
+) -> Result<(), CompressError> {
+    if index >= 16 {
+        return Err(CompressError::InvalidSkippableIndex);
+    }
+    if data.len() as u64 > u32::MAX as u64 {
+        return Err(CompressError::InputTooLarge);
+    }
+
+    atomic(out, |out| {
+        extend(out, &(SKIPPABLE_MAGIC + index as u32).to_le_bytes())?;
+        extend(out, &(data.len() as u32).to_le_bytes())?;
+        extend(out, data)
+    })
+}
+
+fn compress_frame<B: Buf<u8>>(
+    input: &[u8],
+    dict: &[u8],
+    prepared: Option<&PreparedDictionary<'_>>,
+    out: &mut B,
+    prefs: &FramePreferences,
+) -> Result<(), CompressError> {
+    let content_size = if prefs.content_size {
+        Some(input.len() as u64)
+    } else {
+        None
+    };
+    write_header(out, prefs, content_size)?;
+
+    let block_size = prefs.block_size.size();
+    let mut start = 0;
+    while start < input.len() {
+        let end = input.len().min(start + block_size);
+
+        // every independent block can only reference the dictionary,
+        // while linked blocks can also reference the previous blocks
+        if prefs.independent_blocks || start == 0 {
+            let window = Window::new(dict, &input[start..end]);
+            write_block(out, window, 0, prepared, prefs)?;
+        } else if start >= WINDOW_SIZE {
+            let window = Window::new(&[], &input[start - WINDOW_SIZE..end]);
+            write_block(out, window, WINDOW_SIZE, None, prefs)?;
+        } else {
+            write_block(out, Window::new(dict, &input[..end]), start, None, prefs)?;
+        }
+
+        start = end;
+    }
+
+    // the end mark
+    extend(out, &0u32.to_le_bytes())?;
+
+    if prefs.content_checksum {
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(input);
+        extend(out, &(hasher.finish() as u32).to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn extend<B: Buf<u8>>(out: &mut B, bytes: &[u8]) -> Result<(), CompressError> {
+    if out.extend(bytes) {
+        Ok(())
+    } else {
+        Err(CompressError::MemoryLimitExceeded)
+    }
+}
+
+/// Runs `f` and removes everything it wrote to `out`, if it fails.
+///
+/// Returns [`CompressError::TruncateFailed`] if `out` can't be shortened again.
+pub(super) fn atomic<B: Buf<u8>>(
+    out: &mut B,
+    f: impl FnOnce(&mut B) -> Result<(), CompressError>,
+) -> Result<(), CompressError> {
+    let len = out.len();
+    let result = f(out);
+    if result.is_err() && !out.truncate(len) {
+        return Err(CompressError::TruncateFailed);
+    }
+    result
+}
+
+/// Writes the magic number and the frame descriptor.
+pub(super) fn write_header<B: Buf<u8>>(
+    out: &mut B,
+    prefs: &FramePreferences,
+    content_size: Option<u64>,
+) -> Result<(), CompressError> {
+    extend(out, &MAGIC.to_le_bytes())?;
+
+    let descriptor = [
+        (VERSION << 6) | prefs.flags().bits(),
+        prefs.block_size.id() << 4,
+    ];
+    let mut hasher = XxHash32::with_seed(0);
+    hasher.write(&descriptor);
+    extend(out, &descriptor)?;
+
+    if let Some(size) = content_size {
+        hasher.write(&size.to_le_bytes());
+        extend(out, &size.to_le_bytes())?;
+    }
+
+    if let Some(id) = prefs.dictionary_id {
+        hasher.write(&id.to_le_bytes());
+        extend(out, &id.to_le_bytes())?;
+    }
+
+    extend(out, &[(hasher.finish() >> 8) as u8])
+}
+
+/// Writes a single data block, including its size and checksum.
+///
+/// The block consists of the input of the `window`, starting at `start`, while the data
+/// in front is only used as a dictionary. If the block can't be compressed, it's stored uncompressed.
+///
+/// The `prepared` dictionary must contain the same data as the dictionary of the window.
+pub(super) fn write_block<B: Buf<u8>>(
+    out: &mut B,
+    window: Window<'_>,
+    start: usize,
+    prepared: Option<&PreparedDictionary<'_>>,
+    prefs: &FramePreferences,
+) -> Result<(), CompressError> {
+    let window = window.with_max_distance(prefs.max_distance);
+    let block = &window.input()[start..];
+    let pos = out.len();
+    // reserve space for the block size, which is only known after compressing
+    extend(out, &[0; 4])?;
+
+    let result = match (prefs.level, prepared) {
+        (Some(level), _) => super::hc::compress_block_hc_ext(window, start, out, level),
+        (None, Some(dict)) => dict.compress(block, out, prefs.acceleration, prefs.max_distance),
+        (None, None) => super::raw::compress_block_ext(window, start, out, prefs.acceleration),
+    };
+
+    let size = match result {
+        Ok(()) if out.len() - pos - 4 < block.len() => (out.len() - pos - 4) as u32,
+        Ok(()) | Err(CompressError::MemoryLimitExceeded) => {
+            if !out.truncate(pos + 4) {
+                return Err(CompressError::TruncateFailed);
+            }
+            extend(out, block)?;
+            block.len() as u32 | UNCOMPRESSED_DATA
+        }
+        Err(err) => return Err(err),
+    };
+
+    out.as_mut_slice()[pos..pos + 4].copy_from_slice(&size.to_le_bytes());
+
+    if prefs.block_checksums {
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(&out.as_slice()[pos + 4..]);
+        extend(out, &(hasher.finish() as u32).to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockSize, FramePreferences};
+    use crate::{
+        decompress, decompress_with_dictionaries, ArrayBuf, Buf, HeapBuf, PreparedDictionary,
+    };
+    use crate::{CompressError, CompressionLevel};
+
+    fn roundtrip(input: &[u8], prefs: &FramePreferences) -> usize {
+        let mut compressed = HeapBuf::new();
+        super::compress_with(input, &mut compressed, prefs).unwrap();
+
+        let mut decompressed = HeapBuf::new();
+        decompress(compressed.as_slice(), &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), input);
+        compressed.len()
+    }
+
+    #[test]
+    fn frame_hello() {
+        let mut buf = ArrayBuf::<u8, 32>::new();
+        super::compress(b"hello\n", &mut buf).unwrap();
+        assert_eq!(
+            buf.as_slice(),
+            base64::decode("BCJNGGRApwYAAIBoZWxsbwoAAAAA+VtrlA==").unwrap()
+        );
+    }
+
+    #[test]
+    fn frame_roundtrip() {
+        let prefs = FramePreferences::new();
+        roundtrip(&[], &prefs);
+        roundtrip(b"The quick brown fox jumps over the lazy dog.", &prefs);
+        roundtrip(
+            &b"The quick brown fox jumps over the lazy dog. ".repeat(1000),
+            &prefs,
+        );
+    }
+
+    #[test]
+    fn frame_multiple_blocks() {
+        let input = (0..9 * 1024 * 1024)
+            .map(|x| (x % 251) as u8)
+            .collect::<Vec<_>>();
+        roundtrip(&input, &FramePreferences::new());
+    }
+
+    #[test]
+    fn frame_preferences() {
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(10_000);
+
+        for &size in &[
+            BlockSize::Max64KB,
+            BlockSize::Max256KB,
+            BlockSize::Max1MB,
+            BlockSize::Max4MB,
+        ] {
+            for &enabled in &[true, false] {
+                let prefs = FramePreferences::new()
+                    .block_size(size)
+                    .block_checksums(enabled)
+                    .content_checksum(!enabled)
+                    .content_size(enabled)
+                    .independent_blocks(!enabled);
+                roundtrip(&input, &prefs);
+            }
+        }
+    }
+
+    #[test]
+    fn frame_compression_level() {
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(10_000);
+
+        let fast = roundtrip(&input, &FramePreferences::new());
+        let high = roundtrip(
+            &input,
+            &FramePreferences::new().compression_level(CompressionLevel::DEFAULT),
+        );
+        assert!(high <= fast);
+    }
+
+    #[test]
+    fn frame_dictionary() {
+        let data = crate::test_data(272 * 1024, 1, &[100, 3000]);
+        let (dict, input) = data.split_at(16 * 1024);
+
+        for &independent in &[true, false] {
+            let prefs = FramePreferences::new()
+                .dictionary_id(7)
+                .independent_blocks(independent);
+
+            let mut compressed = HeapBuf::new();
+            super::compress_with_dict(input, dict, &mut compressed, &prefs).unwrap();
+
+            let mut decompressed = HeapBuf::new();
+            decompress_with_dictionaries(compressed.as_slice(), &mut decompressed, &[(7, dict)])
+                .unwrap();
+            assert_eq!(decompressed.as_slice(), input);
+        }
+    }
+
+    #[test]
+    fn frame_prepared_dictionary() {
+        let data = crate::test_data(272 * 1024, 1, &[100, 3000]);
+        let (dict, input) = data.split_at(16 * 1024);
+        let prepared = PreparedDictionary::new(dict);
+
+        for &independent in &[true, false] {
+            let prefs = FramePreferences::new()
+                .dictionary_id(7)
+                .independent_blocks(independent);
+
+            let mut plain = HeapBuf::new();
+            super::compress_with_dict(input, dict, &mut plain, &prefs).unwrap();
+
+            let mut compressed = HeapBuf::new();
+            super::compress_with_prepared_dict(input, &prepared, &mut compressed, &prefs).unwrap();
+            assert_eq!(compressed.as_slice(), plain.as_slice());
+        }
+    }
+
+    #[test]
+    fn frame_dictionary_without_id() {
+        let prefs = FramePreferences::new();
+        let mut out = HeapBuf::new();
+        assert!(matches!(
+            super::compress_with_dict(b"hello", b"hello", &mut out, &prefs),
+            Err(CompressError::MissingDictionaryId)
+        ));
+
+        let prepared = PreparedDictionary::new(b"hello");
+        assert!(matches!(
+            super::compress_with_prepared_dict(b"hello", &prepared, &mut out, &prefs),
+            Err(CompressError::MissingDictionaryId)
+        ));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn frame_linked_blocks() {
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(10_000);
+
+        let independent = FramePreferences::new();
+        let linked = FramePreferences::new().independent_blocks(false);
+        assert!(roundtrip(&input, &linked) < roundtrip(&input, &independent));
+    }
+
+    /// A buffer that uses the default [`Buf::truncate`], which can't shorten it.
+    struct Growing(Vec<u8>);
+
+    impl Buf<u8> for Growing {
+        fn push(&mut self, item: u8) -> Option<u8> {
+            self.0.push(item);
+            None
+        }
+
+        fn reserve(&mut self, _: usize) -> bool {
+            true
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn as_slice(&self) -> &[u8] {
+            &self.0
+        }
+
+        fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut self.0
+        }
+
+        fn extend(&mut self, buf: &[u8]) -> bool {
+            self.0.extend_from_slice(buf);
+            true
+        }
+    }
+
+    #[test]
+    fn frame_without_truncate() {
+        let mut out = Growing(Vec::new());
+        super::compress(&b"hello ".repeat(100), &mut out).unwrap();
+
+        let mut decompressed = HeapBuf::new();
+        decompress(&out.0, &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), &b"hello ".repeat(100)[..]);
+
+        // an incompressible block has to be replaced by its uncompressed data
+        let input = crate::test_data(1000, 1, &[]);
+        let mut out = Growing(Vec::new());
+        assert!(matches!(
+            super::compress(&input, &mut out),
+            Err(CompressError::TruncateFailed)
+        ));
+    }
+
+    #[test]
+    fn frame_skippable() {
+        let mut buf = ArrayBuf::<u8, 16>::new();
+        super::write_skippable_frame(10, b"meta", &mut buf).unwrap();
+        assert_eq!(
+            buf.as_slice(),
+            [0x5a, 0x2a, 0x4d, 0x18, 4, 0, 0, 0, b'm', b'e', b't', b'a']
+        );
+
+        let mut buf = ArrayBuf::<u8, 8>::new();
+        buf.push(1);
+        assert!(matches!(
+            super::write_skippable_frame(0, b"meta", &mut buf),
+            Err(CompressError::MemoryLimitExceeded)
+        ));
+        assert_eq!(buf.as_slice(), [1]);
+
+        assert!(matches!(
+            super::write_skippable_frame(16, b"meta", &mut buf),
+            Err(CompressError::InvalidSkippableIndex)
+        ));
+    }
+
+    #[test]
+    fn frame_max_distance() {
+        let input = crate::test_data(128 * 1024, 2, &[1000, 20_000]);
+
+        for level in &[None, Some(9), Some(12)] {
+            let mut prefs = FramePreferences::new()
+                .independent_blocks(false)
+                .max_distance(4096);
+            if let Some(level) = level {
+                prefs = prefs.compression_level(CompressionLevel::new(*level).unwrap());
+            }
+
+            let mut compressed = HeapBuf::new();
+            super::compress_with(&input, &mut compressed, &prefs).unwrap();
+            assert!(roundtrip(&input, &prefs) == compressed.len());
+
+            // skip the header and check the matches of every block
+            let mut frame = &compressed.as_slice()[7..];
+            loop {
+                let size = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]);
+                if size == 0 {
+                    break;
+                }
+
+                let len = (size & !super::UNCOMPRESSED_DATA) as usize;
+                if size & super::UNCOMPRESSED_DATA == 0 {
+                    assert!(crate::compress::max_offset(&frame[4..4 + len]) <= 4096);
+                }
+                frame = &frame[4 + len..];
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Implementation of the high compression mode, which finds better matches
+//! by searching through chains of previous positions that share the same hash.
+
+use super::raw::{
+    write_literals, write_sequence, Window, LAST_LITERALS, MAX_DISTANCE, MF_LIMIT, MIN_MATCH,
+};
+use super::CompressError;
+use crate::Buf;
+
+/// Number of bits used to index the hash table.
+const HASH_LOG: u32 = 15;
+const HASH_SIZE: usize = 1 << HASH_LOG;
+
+/// The chain table stores one entry for every position inside the window.
+const CHAIN_SIZE: usize = MAX_DISTANCE + 1;
+
+/// The level that is used by the high compression mode.
+///
+/// Higher levels search through more previous positions to find the
+/// longest match, which results in a better compression ratio, but also
+/// makes the compression slower. The decompression speed is not affected.
+///
+/// Levels 10 to 12 use an optimal parser, which chooses the sequences
+/// that result in the smallest output, instead of always using the longest match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CompressionLevel(u8);
+
+impl CompressionLevel {
+    /// The lowest level, which is the fastest one.
+    pub const MIN: Self = Self(1);
+    /// The level that provides a good tradeoff between speed and ratio.
+    pub const DEFAULT: Self = Self(9);
+    /// The highest level, which provides the best compression ratio.
+    pub const MAX: Self = Self(12);
+
+    /// Create a new compression level.
+    ///
+    /// Returns `None` if `level` is not in the range of `1..=12`.
+    pub const fn new(level: u8) -> Option<Self> {
+        if level >= Self::MIN.0 && level <= Self::MAX.0 {
+            Some(Self(level))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the numeric value of this level.
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+
+    /// The maximum number of previous positions that are compared to find a match.
+    fn search_depth(self) -> usize {
+        match self.0 {
+            10 => 96,
+            11 => 512,
+            12 => 16384,
+            level => 1 << (level - 1),
+        }
+    }
+
+    /// Returns the match length at which the optimal parser stops searching
+    /// for a better one, or `None` if the optimal parser is not used by this level.
+    fn sufficient_len(self) -> Option<usize> {
+        match self.0 {
+            10 => Some(64),
+            11 => Some(128),
+            12 => Some(super::opt::OPT_NUM),
+            _ => None,
+        }
+    }
+}
+
+impl Default for CompressionLevel {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Compresses `input` into a single LZ4 block using the high compression mode.
+///
+/// The output is a regular LZ4 block, which can be decompressed using
+/// [`decompress_block`](crate::decompress::decompress_block), but it's usually
+/// smaller than the output of [`compress_block`](crate::compress::compress_block).
+///
+/// The compressor state takes up about 256KB and the optimal parser that is used
+/// by the highest levels needs another 80KB. With the `alloc` feature enabled,
+/// both are allocated on the heap, otherwise they're stored on the stack.
+///
+/// If `out` runs out of memory, [`CompressError::MemoryLimitExceeded`] is returned and
+/// `out` is left with a partially written block.
+pub fn compress_block_hc<O: Buf<u8>>(
+    input: &[u8],
+    out: &mut O,
+    level: CompressionLevel,
+) -> Result<(), CompressError> {
+    compress_block_hc_ext(Window::new(&[], input), 0, out, level)
+}
+
+/// Compresses `input` into a single LZ4 block using the high compression mode,
+/// while allowing matches to reference the content of `dict`.
+///
+/// See [`compress_block_hc`] and [`compress_block_with_dict`](crate::compress::compress_block_with_dict)
+/// for more information.
+pub fn compress_block_hc_with_dict<O: Buf<u8>>(
+    input: &[u8],
+    dict: &[u8],
+    out: &mut O,
+    level: CompressionLevel,
+) -> Result<(), CompressError> {
+    compress_block_hc_ext(Window::new(dict, input), 0, out, level)
+}
+
+/// Compresses the input of the `window`, starting at `start`, into a single LZ4 block
+/// using the high compression mode.
+///
+/// Matches can reference all data in front of `start`.
+pub(crate) fn compress_block_hc_ext<O: Buf<u8>>(
+    window: Window<'_>,
+    start: usize,
+    out: &mut O,
+    level: CompressionLevel,
+) -> Result<(), CompressError> {
+    let start = window.input_start() + start;
+    let mut chain = HashChain::new(start.saturating_sub(MAX_DISTANCE));
+    compress_with_chain(window, start, &mut chain, out, level)
+}
+
+/// Compresses the window, starting at position `start`, using the given hash chain.
+fn compress_with_chain<O: Buf<u8>>(
+    window: Window<'_>,
+    start: usize,
+    chain: &mut HashChain,
+    out: &mut O,
+    level: CompressionLevel,
+) -> Result<(), CompressError> {
+    if crate::compressed_bound(window.len() - window.input_start()).is_none() {
+        return Err(CompressError::InputTooLarge);
+    }
+
+    let depth = level.search_depth();
+    if let Some(sufficient_len) = level.sufficient_len() {
+        return super::opt::compress_block_opt(window, start, chain, out, depth, sufficient_len);
+    }
+
+    let end = window.len();
+    let mut anchor = start;
+
+    // inputs that are too small can't contain any match
+    if end - start > MF_LIMIT {
+        let match_limit = end - LAST_LITERALS;
+        let search_limit = end - MF_LIMIT;
+        let mut pos = start;
+
+        while pos <= search_limit {
+            let (mut candidate, mut len) = match chain.find(window, pos, match_limit, depth) {
+                Some(m) => m,
+                None => {
+                    pos += 1;
+                    continue;
+                }
+            };
+
+            // lazy matching: if the next position provides a longer match,
+            // emit the current byte as a literal and use that one instead
+            while pos < search_limit {
+                match chain.find(window, pos + 1, match_limit, depth) {
+                    Some((next, next_len)) if next_len > len => {
+                        pos += 1;
+                        candidate = next;
+                        len = next_len;
+                    }
+                    _ => break,
+                }
+            }
+
+            // try to extend the match backwards into the pending literals
+            while pos > anchor
+                && candidate > 0
+                && window.byte(pos - 1) == window.byte(candidate - 1)
+            {
+                pos -= 1;
+                candidate -= 1;
+                len += 1;
+            }
+
+            write_sequence(
+                out,
+                window.slice(anchor, pos),
+                (pos - candidate) as u16,
+                len,
+            )?;
+
+            pos += len;
+            anchor = pos;
+        }
+    }
+
+    write_literals(out, window.slice(anchor, end))
+}
+
+fn hash(seq: u32) -> usize {
+    (seq.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+/// Keeps track of all previous positions inside the window,
+/// grouped by the hash of the 4 bytes at that position.
+pub(super) struct HashChain {
+    /// Maps a hash to the last position with that hash, plus one.
+    #[cfg(feature = "alloc")]
+    table: alloc::boxed::Box<[u32]>,
+    #[cfg(not(feature = "alloc"))]
+    table: [u32; HASH_SIZE],
+    /// Maps a position to the distance of the previous position with the same hash.
+    #[cfg(feature = "alloc")]
+    chain: alloc::boxed::Box<[u16]>,
+    #[cfg(not(feature = "alloc"))]
+    chain: [u16; CHAIN_SIZE],
+    /// All positions before this one are already inserted.
+    next: usize,
+}
+
+impl HashChain {
+    pub(super) fn new(start: usize) -> Self {
+        Self {
+            #[cfg(feature = "alloc")]
+            table: alloc::vec![0; HASH_SIZE].into_boxed_slice(),
+            #[cfg(not(feature = "alloc"))]
+            table: [0; HASH_SIZE],
+            #[cfg(feature = "alloc")]
+            chain: alloc::vec![0; CHAIN_SIZE].into_boxed_slice(),
+            #[cfg(not(feature = "alloc"))]
+            chain: [0; CHAIN_SIZE],
+            next: start,
+        }
+    }
+
+    /// Inserts all positions up to, but not including, `pos`.
+    fn insert(&mut self, window: Window<'_>, pos: usize) {
+        while self.next < pos {
+            let hash = hash(window.read_u32(self.next));
+            let distance = match self.table[hash] as usize {
+                0 => 0,
+                prev => self.next - (prev - 1),
+            };
+
+            // a distance of zero marks the end of the chain
+            self.chain[self.next % CHAIN_SIZE] = if distance > MAX_DISTANCE {
+                0
+            } else {
+                distance as u16
+            };
+            self.table[hash] = self.next as u32 + 1;
+            self.next += 1;
+        }
+    }
+
+    /// Searches the longest match for the bytes at `pos`, which must end before `limit`.
+    ///
+    /// Returns the position and the length of the match.
+    pub(super) fn find(
+        &mut self,
+        window: Window<'_>,
+        pos: usize,
+        limit: usize,
+        depth: usize,
+    ) -> Option<(usize, usize)> {
+        self.insert(window, pos);
+
+        let seq = window.read_u32(pos);
+        let mut candidate = match self.table[hash(seq)] as usize {
+            0 => return None,
+            candidate => candidate - 1,
+        };
+
+        let mut best = None;
+        let mut best_len = 0;
+        for _ in 0..depth {
+            // the optimal parser may search positions again, after later ones
+            // were already inserted
+            if candidate >= pos {
+                // skip the candidate
+            } else if pos - candidate > window.max_distance() {
+                break;
+            } else if window.read_u32(candidate) == seq {
+                let len =
+                    MIN_MATCH + window.count_same(candidate + MIN_MATCH, pos + MIN_MATCH, limit);
+                if len > best_len {
+                    best = Some((candidate, len));
+                    best_len = len;
+                }
+            }
+
+            match self.chain[candidate % CHAIN_SIZE] as usize {
+                0 => break,
+                distance if distance > candidate => break,
+                distance => candidate -= distance,
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressionLevel;
+    use crate::{
+        compress_block, compressed_bound, decompress_block, decompress_block_with_dict, Buf,
+        HeapBuf,
+    };
+
+    fn roundtrip(input: &[u8], level: CompressionLevel) -> usize {
+        let mut compressed = HeapBuf::new();
+        super::compress_block_hc(input, &mut compressed, level).unwrap();
+        assert!(compressed.len() <= compressed_bound(input.len()).unwrap());
+
+        let mut decompressed = HeapBuf::new();
+        decompress_block(compressed.as_slice(), &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), input);
+        compressed.len()
+    }
+
+    #[test]
+    fn level() {
+        assert_eq!(CompressionLevel::new(0), None);
+        assert_eq!(CompressionLevel::new(1), Some(CompressionLevel::MIN));
+        assert_eq!(CompressionLevel::new(12), Some(CompressionLevel::MAX));
+        assert_eq!(CompressionLevel::new(13), None);
+    }
+
+    #[test]
+    fn block_hc_roundtrip() {
+        let text = b"The quick brown fox jumps over the lazy dog. The lazy dog sleeps. ".repeat(50);
+        for level in 1..=12 {
+            let level = CompressionLevel::new(level).unwrap();
+            assert_eq!(roundtrip(&[], level), 0);
+            roundtrip(b"hello", level);
+            roundtrip(&[b'a'; 1000], level);
+            roundtrip(&text, level);
+        }
+    }
+
+    #[test]
+    fn block_hc_with_dict() {
+        let data = crate::test_data(18 * 1024, 1, &[100, 3000]);
+        let (dict, input) = data.split_at(16 * 1024);
+
+        for level in 1..=12 {
+            let level = CompressionLevel::new(level).unwrap();
+            let mut compressed = HeapBuf::new();
+            super::compress_block_hc_with_dict(input, dict, &mut compressed, level).unwrap();
+
+            let mut decompressed = HeapBuf::new();
+            decompress_block_with_dict(compressed.as_slice(), dict, &mut decompressed).unwrap();
+            assert_eq!(decompressed.as_slice(), input);
+            assert!(compressed.len() < roundtrip(input, level));
+        }
+    }
+
+    #[test]
+    fn block_hc_better_ratio() {
+        let input = crate::test_data(32 * 1024, 1, &[100, 3000]);
+
+        let mut fast = HeapBuf::new();
+        compress_block(&input, &mut fast).unwrap();
+
+        let min = roundtrip(&input, CompressionLevel::MIN);
+        let max = roundtrip(&input, CompressionLevel::MAX);
+        assert!(max <= min);
+        assert!(max < fast.len());
+    }
+}
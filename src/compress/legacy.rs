@@ -0,0 +1,68 @@
+//! Compression into the legacy LZ4 frame format.
+
+use super::framed::extend;
+use super::CompressError;
+use crate::decompress::{LEGACY_BLOCK_SIZE, LEGACY_MAGIC};
+use crate::Buf;
+
+/// Compresses `input` using the legacy LZ4 frame format, which starts with
+/// the magic number `0x184C2102`.
+///
+/// This is the format produced by `lz4 -l` and expected by the Linux kernel.
+/// It doesn't support any checksums or dictionaries, so the [frame format](super::compress)
+/// should be preferred for anything else. The input is split into independent blocks
+/// of 8MB and the result can be decompressed using
+/// [`decompress_legacy`](crate::decompress::decompress_legacy).
+///
+/// If `out` runs out of memory, [`CompressError::MemoryLimitExceeded`] is returned.
+pub fn compress_legacy<B: Buf<u8>>(input: &[u8], out: &mut B) -> Result<(), CompressError> {
+    extend(out, &LEGACY_MAGIC.to_le_bytes())?;
+
+    for block in input.chunks(LEGACY_BLOCK_SIZE) {
+        let pos = out.len();
+        // reserve space for the block size, which is only known after compressing
+        extend(out, &[0; 4])?;
+        super::compress_block(block, out)?;
+
+        let size = (out.len() - pos - 4) as u32;
+        out.as_mut_slice()[pos..pos + 4].copy_from_slice(&size.to_le_bytes());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{decompress_legacy, Buf, HeapBuf};
+
+    fn roundtrip(input: &[u8]) -> HeapBuf<u8> {
+        let mut compressed = HeapBuf::new();
+        super::compress_legacy(input, &mut compressed).unwrap();
+
+        let mut decompressed = HeapBuf::new();
+        decompress_legacy(compressed.as_slice(), &mut decompressed).unwrap();
+        assert!(decompressed.as_slice() == input);
+        compressed
+    }
+
+    #[test]
+    fn legacy_hello() {
+        let compressed = roundtrip(b"hello ");
+        assert_eq!(
+            compressed.as_slice(),
+            [
+                0x02, 0x21, 0x4c, 0x18, 0x07, 0x00, 0x00, 0x00, 0x60, b'h', b'e', b'l', b'l', b'o',
+                b' '
+            ]
+        );
+        assert_eq!(roundtrip(&[]).as_slice(), [0x02, 0x21, 0x4c, 0x18]);
+    }
+
+    #[test]
+    fn legacy_multiple_blocks() {
+        let input = (0..9 * 1024 * 1024)
+            .map(|x| (x % 251) as u8)
+            .collect::<Vec<_>>();
+        roundtrip(&input);
+    }
+}
@@ -0,0 +1,253 @@
+//! Implementation of the optimal parser, which is used by the highest
+//! compression levels.
+//!
+//! Instead of always taking the longest match, the parser calculates the
+//! price (the number of encoded bytes) of every possible way to encode the
+//! next few kilobytes and then chooses the cheapest one.
+
+use super::hc::HashChain;
+use super::raw::{write_literals, write_sequence, Window, LAST_LITERALS, MF_LIMIT, MIN_MATCH};
+use super::CompressError;
+use crate::Buf;
+
+/// The maximum number of positions the parser looks ahead.
+pub(super) const OPT_NUM: usize = 1 << 12;
+
+/// A node in the graph of possible encodings.
+#[derive(Clone, Copy)]
+struct Node {
+    /// The price of the cheapest way to reach this position.
+    price: u32,
+    /// The number of literals in front of this position.
+    litlen: u32,
+    /// The length of the match that ends at this position, or `0` if
+    /// this position is reached using a literal.
+    len: u32,
+    /// The offset of the match that ends at this position.
+    offset: u16,
+    /// The next position on the cheapest path, which is only set after the
+    /// parsing is finished.
+    next: u32,
+}
+
+impl Node {
+    const UNREACHED: Self = Self {
+        price: u32::MAX,
+        litlen: 0,
+        len: 0,
+        offset: 0,
+        next: 0,
+    };
+}
+
+/// Returns the number of bytes that are required to encode a length,
+/// whose first 15 values are stored inside the token.
+fn length_price(len: usize) -> u32 {
+    if len >= 15 {
+        1 + (len as u32 - 15) / 255
+    } else {
+        0
+    }
+}
+
+/// The price of `litlen` literals, excluding the token.
+fn literal_price(litlen: usize) -> u32 {
+    litlen as u32 + length_price(litlen)
+}
+
+/// The price of a match with the given length, including the token and the offset.
+fn match_price(len: usize) -> u32 {
+    1 + 2 + length_price(len - MIN_MATCH)
+}
+
+/// Compresses the window, starting at position `start`, into a single LZ4 block
+/// using the optimal parser.
+///
+/// `depth` is the maximum number of positions that are compared to find
+/// a match and matches that are at least `sufficient_len` bytes long are
+/// used immediately.
+pub(super) fn compress_block_opt<O: Buf<u8>>(
+    window: Window<'_>,
+    start: usize,
+    chain: &mut HashChain,
+    out: &mut O,
+    depth: usize,
+    sufficient_len: usize,
+) -> Result<(), CompressError> {
+    let end = window.len();
+    let mut anchor = start;
+
+    // inputs that are too small can't contain any match
+    if end - start > MF_LIMIT {
+        let match_limit = end - LAST_LITERALS;
+        let search_limit = end - MF_LIMIT;
+
+        #[cfg(feature = "alloc")]
+        let mut opt = alloc::vec![Node::UNREACHED; OPT_NUM + 1];
+        #[cfg(not(feature = "alloc"))]
+        let mut opt = [Node::UNREACHED; OPT_NUM + 1];
+        let mut pos = start;
+
+        while pos <= search_limit {
+            let (offset, len) = match chain.find(window, pos, match_limit, depth) {
+                Some((candidate, len)) => (pos - candidate, len),
+                None => {
+                    pos += 1;
+                    continue;
+                }
+            };
+
+            // long matches are good enough, so don't spend time on finding a better one
+            if len >= sufficient_len {
+                write_sequence(out, window.slice(anchor, pos), offset as u16, len)?;
+                pos += len;
+                anchor = pos;
+                continue;
+            }
+
+            let litlen = pos - anchor;
+            opt[0] = Node {
+                price: literal_price(litlen),
+                litlen: litlen as u32,
+                ..Node::UNREACHED
+            };
+
+            let mut last = 0;
+            let mut forced = None;
+            let mut cur = 0;
+            loop {
+                let found = if cur == 0 {
+                    Some((offset, len))
+                } else if pos + cur <= search_limit {
+                    chain
+                        .find(window, pos + cur, match_limit, depth)
+                        .map(|(candidate, len)| (pos + cur - candidate, len))
+                } else {
+                    None
+                };
+
+                if let Some((offset, len)) = found {
+                    // the match is too long to be parsed, so use it directly
+                    if len >= sufficient_len || cur + len > OPT_NUM {
+                        forced = Some((offset, len));
+                        break;
+                    }
+
+                    for len in MIN_MATCH..=len {
+                        let target = cur + len;
+                        while last < target {
+                            last += 1;
+                            opt[last] = Node::UNREACHED;
+                        }
+
+                        let price = opt[cur].price + match_price(len);
+                        if price < opt[target].price {
+                            opt[target] = Node {
+                                price,
+                                litlen: 0,
+                                len: len as u32,
+                                offset: offset as u16,
+                                next: 0,
+                            };
+                        }
+                    }
+                }
+
+                if cur >= last {
+                    break;
+                }
+
+                // try to reach the next position using a literal
+                let node = opt[cur];
+                let litlen = node.litlen as usize + 1;
+                let price = node.price - literal_price(litlen - 1) + literal_price(litlen);
+                if price < opt[cur + 1].price {
+                    opt[cur + 1] = Node {
+                        price,
+                        litlen: litlen as u32,
+                        ..Node::UNREACHED
+                    };
+                }
+
+                cur += 1;
+            }
+
+            // walk the cheapest path backwards, to link every node to the next one
+            let end = cur;
+            while cur > 0 {
+                let prev = match opt[cur].len as usize {
+                    0 => cur - 1,
+                    len => cur - len,
+                };
+                opt[prev].next = cur as u32;
+                cur = prev;
+            }
+
+            while cur < end {
+                let next = opt[cur].next as usize;
+                let node = opt[next];
+                if node.len != 0 {
+                    write_sequence(
+                        out,
+                        window.slice(anchor, pos + cur),
+                        node.offset,
+                        node.len as usize,
+                    )?;
+                    anchor = pos + next;
+                }
+                cur = next;
+            }
+
+            pos += end;
+            if let Some((offset, len)) = forced {
+                write_sequence(out, window.slice(anchor, pos), offset as u16, len)?;
+                pos += len;
+                anchor = pos;
+            }
+        }
+    }
+
+    write_literals(out, window.slice(anchor, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compress_block_hc, decompress_block, Buf, CompressionLevel, HeapBuf};
+
+    fn compress(input: &[u8], level: u8) -> usize {
+        let mut compressed = HeapBuf::new();
+        let level = CompressionLevel::new(level).unwrap();
+        compress_block_hc(input, &mut compressed, level).unwrap();
+
+        let mut decompressed = HeapBuf::new();
+        decompress_block(compressed.as_slice(), &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), input);
+        compressed.len()
+    }
+
+    #[test]
+    fn block_opt_lengths() {
+        // mix incompressible runs of different lengths with repeated content,
+        // to produce long literal runs and matches
+        let mut input = Vec::new();
+        for len in &[3usize, 14, 15, 16, 270, 300, 1000, 5000] {
+            input.extend(crate::test_data(*len, *len as u32, &[]));
+            input.extend_from_slice(
+                &b"The quick brown fox jumps over the lazy dog. ".repeat(*len / 10),
+            );
+        }
+
+        for level in 10..=12 {
+            compress(&input, level);
+        }
+    }
+
+    #[test]
+    fn block_opt_better_ratio() {
+        let input = crate::test_data(32 * 1024, 1, &[100, 3000]);
+        let hc = compress(&input, 9);
+        for level in 10..=12 {
+            assert!(compress(&input, level) <= hc);
+        }
+    }
+}
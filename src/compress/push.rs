@@ -0,0 +1,353 @@
+//! Push-based compression into the LZ4 frame format, which doesn't depend
+//! on `std::io` or an allocator.
+
+use super::framed::{atomic, extend, write_block, write_header, WINDOW_SIZE};
+use super::raw::Window;
+use super::{CompressError, FramePreferences};
+use crate::{ArrayBuf, Buf};
+use core::hash::Hasher;
+use twox_hash::XxHash32;
+
+/// Compresses the data that is pushed into it into LZ4 frames, while using
+/// only a fixed amount of memory.
+///
+/// The input is collected in a buffer of `N` bytes, which is compressed into a block
+/// once it is full. Therefore blocks are never larger than `N` bytes, even if the
+/// [block size](FramePreferences::block_size) allows larger ones. Linked blocks
+/// keep up to 64KB, but at most half of the buffer, of the previous input around.
+///
+/// Every step writes whole blocks into the `out` buffer. If `out` doesn't have
+/// enough space left, [`CompressError::MemoryLimitExceeded`] is returned and
+/// nothing is written, so the caller can empty `out` and retry the same step.
+/// If `out` can't remove the partially written data, because it doesn't support
+/// [`Buf::truncate`], [`CompressError::TruncateFailed`] is returned instead.
+///
+/// Since the size of the content is unknown when writing the header,
+/// [`FramePreferences::content_size`] is ignored.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{ArrayBuf, Buf, CompressError, PushEncoder};
+///
+/// let mut flash = Vec::new();
+/// let mut page = ArrayBuf::<u8, 8192>::new();
+/// let mut encoder = PushEncoder::<4096>::new();
+///
+/// for line in (0..1000).map(|i| format!("sensor: {}\n", i % 7)) {
+///     let mut input = line.as_bytes();
+///     while !input.is_empty() {
+///         match encoder.feed(input, &mut page) {
+///             Ok(consumed) => input = &input[consumed..],
+///             Err(CompressError::MemoryLimitExceeded) => {
+///                 flash.extend_from_slice(page.as_slice());
+///                 page.truncate(0);
+///             }
+///             Err(err) => panic!("{}", err),
+///         }
+///     }
+/// }
+///
+/// if encoder.finish(&mut page).is_err() {
+///     flash.extend_from_slice(page.as_slice());
+///     page.truncate(0);
+///     encoder.finish(&mut page).unwrap();
+/// }
+/// flash.extend_from_slice(page.as_slice());
+/// ```
+pub struct PushEncoder<const N: usize> {
+    prefs: FramePreferences,
+    /// The data of the previous blocks that can still be referenced,
+    /// followed by the input of the current block.
+    buffer: ArrayBuf<u8, N>,
+    /// The position inside `buffer` where the input of the current block starts.
+    start: usize,
+    hasher: XxHash32,
+    header_written: bool,
+}
+
+impl<const N: usize> PushEncoder<N> {
+    /// Creates a new encoder, that writes frames with the default preferences.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero.
+    pub fn new() -> Self {
+        Self::with_preferences(FramePreferences::new())
+    }
+
+    /// Creates a new encoder, that writes frames with the given preferences.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero, because such an encoder couldn't consume any input.
+    pub fn with_preferences(prefs: FramePreferences) -> Self {
+        assert!(N > 0, "the input buffer of a `PushEncoder` can't be empty");
+        Self {
+            prefs: prefs.content_size(false),
+            buffer: ArrayBuf::new(),
+            start: 0,
+            hasher: XxHash32::with_seed(0),
+            header_written: false,
+        }
+    }
+
+    /// Collects the next chunk of input and returns how many bytes of it were consumed.
+    ///
+    /// If the buffer is already full, its content is compressed into a block first.
+    /// The number of consumed bytes is less than the length of `input` if the buffer
+    /// becomes full, so this has to be called again with the remaining input.
+    pub fn feed<O: Buf<u8>>(&mut self, input: &[u8], out: &mut O) -> Result<usize, CompressError> {
+        self.write_header(out)?;
+        if self.pending() == self.block_size() {
+            self.write_pending(out)?;
+        }
+
+        let count = input.len().min(self.block_size() - self.pending());
+        self.buffer.extend(&input[..count]);
+        Ok(count)
+    }
+
+    /// Compresses the collected input into a block, even if the buffer isn't full yet.
+    ///
+    /// Flushing often results in small blocks, which worsens the compression ratio.
+    pub fn flush<O: Buf<u8>>(&mut self, out: &mut O) -> Result<(), CompressError> {
+        self.write_header(out)?;
+        self.write_pending(out)
+    }
+
+    /// Compresses the collected input and ends the frame by writing
+    /// the end mark and the content checksum.
+    ///
+    /// Afterwards, the encoder starts a new frame on the next call to [`feed`](Self::feed).
+    pub fn finish<O: Buf<u8>>(&mut self, out: &mut O) -> Result<(), CompressError> {
+        self.flush(out)?;
+
+        let mut end = [0; 8];
+        end[4..].copy_from_slice(&(self.hasher.finish() as u32).to_le_bytes());
+        let end = if self.prefs.content_checksum {
+            &end[..]
+        } else {
+            &end[..4]
+        };
+        atomic(out, |out| extend(out, end))?;
+
+        self.buffer.truncate(0);
+        self.start = 0;
+        self.hasher = XxHash32::with_seed(0);
+        self.header_written = false;
+        Ok(())
+    }
+
+    /// The maximum number of bytes the current block can contain.
+    fn block_size(&self) -> usize {
+        self.prefs.block_size.size().min(N - self.start)
+    }
+
+    /// The number of bytes that were collected for the current block.
+    fn pending(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+
+    fn write_header<O: Buf<u8>>(&mut self, out: &mut O) -> Result<(), CompressError> {
+        if !self.header_written {
+            atomic(out, |out| write_header(out, &self.prefs, None))?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    fn write_pending<O: Buf<u8>>(&mut self, out: &mut O) -> Result<(), CompressError> {
+        if self.pending() == 0 {
+            return Ok(());
+        }
+
+        let window = Window::new(&[], self.buffer.as_slice());
+        atomic(out, |out| {
+            write_block(out, window, self.start, None, &self.prefs)
+        })?;
+        self.hasher.write(&self.buffer.as_slice()[self.start..]);
+
+        // keep only the data that can be referenced by the next block
+        let keep = if self.prefs.independent_blocks {
+            0
+        } else {
+            WINDOW_SIZE.min(N / 2)
+        };
+        let len = self.buffer.len();
+        if len > keep {
+            self.buffer.as_mut_slice().copy_within(len - keep.., 0);
+            self.buffer.truncate(keep);
+        }
+        self.start = self.buffer.len();
+        Ok(())
+    }
+}
+
+impl<const N: usize> Default for PushEncoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PushEncoder;
+    use crate::{
+        compress_with, decompress, ArrayBuf, Buf, CompressError, FramePreferences, HeapBuf,
+    };
+
+    /// Compresses `input` in chunks of `size` bytes into pages of `P` bytes.
+    fn encode<const N: usize, const P: usize>(
+        encoder: &mut PushEncoder<N>,
+        input: &[u8],
+        size: usize,
+    ) -> Vec<u8> {
+        let mut pages = Vec::new();
+        let mut page = ArrayBuf::<u8, P>::new();
+
+        for mut chunk in input.chunks(size) {
+            while !chunk.is_empty() {
+                match encoder.feed(chunk, &mut page) {
+                    Ok(consumed) => chunk = &chunk[consumed..],
+                    Err(CompressError::MemoryLimitExceeded) => {
+                        pages.extend_from_slice(page.as_slice());
+                        page.truncate(0);
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+        }
+
+        if let Err(CompressError::MemoryLimitExceeded) = encoder.finish(&mut page) {
+            pages.extend_from_slice(page.as_slice());
+            page.truncate(0);
+            encoder.finish(&mut page).unwrap();
+        }
+        pages.extend_from_slice(page.as_slice());
+        pages
+    }
+
+    fn decode(frame: &[u8]) -> HeapBuf<u8> {
+        let mut out = HeapBuf::new();
+        decompress(frame, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn push_small_buffers() {
+        let input = crate::test_data(90 * 1024, 1, &[100, 3000, 20_000]);
+        for &independent_blocks in &[true, false] {
+            let prefs = FramePreferences::new()
+                .independent_blocks(independent_blocks)
+                .block_checksums(true);
+            let mut encoder = PushEncoder::<4096>::with_preferences(prefs);
+            for &size in &[1, 100, 5000] {
+                let frame = encode::<4096, 8192>(&mut encoder, &input, size);
+                assert!(decode(&frame).as_slice() == &input[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn push_matches_compress() {
+        let input = crate::test_data(256 * 1024, 1, &[100, 3000, 20_000]);
+        for &independent_blocks in &[true, false] {
+            let prefs = FramePreferences::new().independent_blocks(independent_blocks);
+            let mut expected = HeapBuf::new();
+            compress_with(&input, &mut expected, &prefs).unwrap();
+
+            let mut encoder = PushEncoder::<{ 128 * 1024 }>::with_preferences(prefs);
+            let frame = encode::<{ 128 * 1024 }, { 80 * 1024 }>(&mut encoder, &input, 3000);
+            assert!(frame == expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn push_flush() {
+        let mut encoder = PushEncoder::<1024>::new();
+        let mut out = ArrayBuf::<u8, 64>::new();
+        assert_eq!(encoder.feed(b"hello ", &mut out).unwrap(), 6);
+        encoder.flush(&mut out).unwrap();
+        let flushed = out.len();
+        assert!(flushed > 7 + 6);
+
+        assert_eq!(encoder.feed(b"world", &mut out).unwrap(), 5);
+        assert_eq!(out.len(), flushed);
+        encoder.finish(&mut out).unwrap();
+        assert_eq!(decode(out.as_slice()).as_slice(), b"hello world");
+    }
+
+    #[test]
+    fn push_out_of_memory() {
+        let mut encoder = PushEncoder::<1024>::new();
+        let mut out = ArrayBuf::<u8, 4>::new();
+        assert!(matches!(
+            encoder.feed(b"hello", &mut out),
+            Err(CompressError::MemoryLimitExceeded)
+        ));
+        assert_eq!(out.len(), 0);
+
+        let mut out = ArrayBuf::<u8, 64>::new();
+        assert_eq!(encoder.feed(b"hello", &mut out).unwrap(), 5);
+        encoder.finish(&mut out).unwrap();
+        assert_eq!(decode(out.as_slice()).as_slice(), b"hello");
+    }
+
+    /// A buffer with a fixed capacity, that uses the default [`Buf::truncate`].
+    struct Page(Vec<u8>);
+
+    impl Buf<u8> for Page {
+        fn push(&mut self, item: u8) -> Option<u8> {
+            if self.reserve(1) {
+                self.0.push(item);
+                None
+            } else {
+                Some(item)
+            }
+        }
+
+        fn reserve(&mut self, count: usize) -> bool {
+            self.0.len() + count <= 5
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn as_slice(&self) -> &[u8] {
+            &self.0
+        }
+
+        fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut self.0
+        }
+
+        fn extend(&mut self, buf: &[u8]) -> bool {
+            let fits = self.reserve(buf.len());
+            if fits {
+                self.0.extend_from_slice(buf);
+            }
+            fits
+        }
+    }
+
+    #[test]
+    fn push_without_truncate() {
+        let mut encoder = PushEncoder::<1024>::new();
+        let mut out = Page(Vec::new());
+
+        // only the magic number of the header fits and can't be removed again
+        assert!(matches!(
+            encoder.feed(b"hello", &mut out),
+            Err(CompressError::TruncateFailed)
+        ));
+        assert_eq!(out.len(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn push_empty_buffer() {
+        PushEncoder::<0>::new();
+    }
+}
@@ -0,0 +1,510 @@
+//! Implementation of compressing raw LZ4-blocks.
+
+use super::CompressError;
+use crate::Buf;
+
+/// The minimum length of a match.
+pub(super) const MIN_MATCH: usize = 4;
+
+/// The last match must start at least 12 bytes before the end of the block.
+pub(super) const MF_LIMIT: usize = 12;
+
+/// The last 5 bytes of a block are always literals.
+pub(super) const LAST_LITERALS: usize = 5;
+
+/// The largest offset that can be encoded in a sequence.
+pub(super) const MAX_DISTANCE: usize = 0xFFFF;
+
+/// Number of bits used to index the hash table.
+const HASH_LOG: u32 = 12;
+pub(super) const HASH_SIZE: usize = 1 << HASH_LOG;
+
+/// Controls how fast the compressor starts skipping positions
+/// if it doesn't find any matches.
+const SKIP_TRIGGER: u32 = 6;
+
+/// Larger accelerations would skip the whole window at once.
+const MAX_ACCELERATION: u32 = 1 << 16;
+
+/// Compresses `input` into a single LZ4 block.
+///
+/// The compressed data will be written into the `out` buffer and can be decompressed
+/// using [`decompress_block`](crate::decompress::decompress_block).
+/// The output will never be larger than [`compressed_bound`](crate::compressed_bound)
+/// of the input length, so this can be used to make sure that `out` has enough memory.
+///
+/// If `out` runs out of memory, [`CompressError::MemoryLimitExceeded`] is returned and
+/// `out` is left with a partially written block.
+pub fn compress_block<O: Buf<u8>>(input: &[u8], out: &mut O) -> Result<(), CompressError> {
+    compress_block_fast(input, out, 1)
+}
+
+/// Compresses `input` into a single LZ4 block using the given acceleration.
+///
+/// The acceleration controls how fast the compressor skips positions that didn't
+/// result in a match. Higher values make the compression faster, but result in a
+/// worse compression ratio. An acceleration of `1` behaves exactly like
+/// [`compress_block`], values smaller than `1` are treated as `1`.
+///
+/// See [`compress_block`] for more information.
+pub fn compress_block_fast<O: Buf<u8>>(
+    input: &[u8],
+    out: &mut O,
+    acceleration: u32,
+) -> Result<(), CompressError> {
+    compress_block_ext(Window::new(&[], input), 0, out, acceleration)
+}
+
+/// Compresses `input` into a single LZ4 block, while allowing matches to
+/// reference the content of `dict`.
+///
+/// This improves the compression ratio of small inputs, that share a lot of content
+/// with the dictionary. Only the last 64KB of the dictionary are used and the
+/// block must be decompressed using the same dictionary, by calling
+/// [`decompress_block_with_dict`](crate::decompress::decompress_block_with_dict).
+///
+/// See [`compress_block`] for more information.
+pub fn compress_block_with_dict<O: Buf<u8>>(
+    input: &[u8],
+    dict: &[u8],
+    out: &mut O,
+) -> Result<(), CompressError> {
+    compress_block_ext(Window::new(dict, input), 0, out, 1)
+}
+
+/// Compresses `input` into a single LZ4 block, whose matches reference at most
+/// `max_distance` bytes in front of them.
+///
+/// The offset of a match can be up to 64KB, so a decoder usually has to keep the last 64KB
+/// of output around. Limiting the distance allows decoders with less memory, like one that
+/// only keeps the last 4KB in a ring buffer, to decompress the block, but makes the
+/// compression ratio worse.
+///
+/// See [`compress_block`] for more information.
+pub fn compress_block_with_max_distance<O: Buf<u8>>(
+    input: &[u8],
+    out: &mut O,
+    max_distance: u16,
+) -> Result<(), CompressError> {
+    let window = Window::new(&[], input).with_max_distance(max_distance);
+    compress_block_ext(window, 0, out, 1)
+}
+
+/// Compresses the input of the `window`, starting at `start`, into a single LZ4 block.
+///
+/// Matches can reference all data in front of `start`, which is used to compress
+/// linked blocks or blocks that use a dictionary.
+pub(crate) fn compress_block_ext<O: Buf<u8>>(
+    window: Window<'_>,
+    start: usize,
+    out: &mut O,
+    acceleration: u32,
+) -> Result<(), CompressError> {
+    // the table maps the hash of 4 bytes to the last position they were seen at
+    let mut table = [0u32; HASH_SIZE];
+    let start = window.dict.len() + start;
+    index(window, start, &mut table);
+    compress_with_table(window, start, &mut table, out, acceleration)
+}
+
+/// Inserts the positions in front of `end`, that are reachable from `end`, into the table.
+///
+/// Only positions whose bytes are completely in front of `end` are inserted, and only every
+/// third one, since inserting all of them would overwrite most of the older entries in the small table.
+pub(super) fn index(window: Window<'_>, end: usize, table: &mut [u32; HASH_SIZE]) {
+    let last = end.saturating_sub(MIN_MATCH - 1);
+    for pos in (end.saturating_sub(MAX_DISTANCE)..last).step_by(3) {
+        table[hash(window.read_u32(pos))] = pos as u32;
+    }
+}
+
+/// Compresses the window, starting at position `start`, using the already filled hash table.
+pub(super) fn compress_with_table<O: Buf<u8>>(
+    window: Window<'_>,
+    start: usize,
+    table: &mut [u32; HASH_SIZE],
+    out: &mut O,
+    acceleration: u32,
+) -> Result<(), CompressError> {
+    if crate::compressed_bound(window.input.len()).is_none() {
+        return Err(CompressError::InputTooLarge);
+    }
+
+    let end = window.len();
+    let mut anchor = start;
+
+    // inputs that are too small can't contain any match
+    if end - start > MF_LIMIT {
+        let match_limit = end - LAST_LITERALS;
+        let search_limit = end - MF_LIMIT;
+
+        let acceleration = acceleration.clamp(1, MAX_ACCELERATION) as usize;
+        let mut pos = start;
+        let mut attempts = acceleration << SKIP_TRIGGER;
+
+        while pos <= search_limit {
+            let seq = window.read_u32(pos);
+            let slot = hash(seq);
+            let candidate = table[slot] as usize;
+            table[slot] = pos as u32;
+
+            if candidate >= pos
+                || pos - candidate > window.max_distance()
+                || window.read_u32(candidate) != seq
+            {
+                // the longer we don't find a match, the more positions are skipped
+                pos += attempts >> SKIP_TRIGGER;
+                attempts += 1;
+                continue;
+            }
+
+            // try to extend the match backwards into the pending literals
+            let (mut start, mut candidate) = (pos, candidate);
+            while start > anchor
+                && candidate > 0
+                && window.byte(start - 1) == window.byte(candidate - 1)
+            {
+                start -= 1;
+                candidate -= 1;
+            }
+
+            let len = MIN_MATCH
+                + window.count_same(candidate + MIN_MATCH, start + MIN_MATCH, match_limit);
+
+            write_sequence(
+                out,
+                window.slice(anchor, start),
+                (start - candidate) as u16,
+                len,
+            )?;
+
+            pos = start + len;
+            anchor = pos;
+            attempts = acceleration << SKIP_TRIGGER;
+
+            // the position right before the end of the match is a good candidate
+            // for future matches
+            if pos <= search_limit {
+                table[hash(window.read_u32(pos - 2))] = (pos - 2) as u32;
+            }
+        }
+    }
+
+    write_literals(out, window.slice(anchor, end))
+}
+
+fn hash(seq: u32) -> usize {
+    (seq.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+/// The data that is visible to the compressor, which consists of
+/// a dictionary, followed by the input that is compressed.
+///
+/// All positions refer to the concatenation of both.
+#[derive(Clone, Copy)]
+pub(crate) struct Window<'a> {
+    dict: &'a [u8],
+    input: &'a [u8],
+    max_distance: usize,
+}
+
+impl<'a> Window<'a> {
+    /// Create a new window, that only contains the part of `dict`
+    /// which can be referenced from the input.
+    pub(crate) fn new(dict: &'a [u8], input: &'a [u8]) -> Self {
+        let dict = &dict[dict.len().saturating_sub(MAX_DISTANCE)..];
+        Self {
+            dict,
+            input,
+            max_distance: MAX_DISTANCE,
+        }
+    }
+
+    /// Limits the offset of all matches to `distance`.
+    pub(crate) fn with_max_distance(mut self, distance: u16) -> Self {
+        self.max_distance = distance as usize;
+        self
+    }
+
+    /// Returns the largest offset a match can have.
+    pub(super) fn max_distance(&self) -> usize {
+        self.max_distance
+    }
+
+    /// Returns the position where the input starts.
+    pub(super) fn input_start(&self) -> usize {
+        self.dict.len()
+    }
+
+    pub(super) fn input(&self) -> &'a [u8] {
+        self.input
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.dict.len() + self.input.len()
+    }
+
+    pub(super) fn byte(&self, pos: usize) -> u8 {
+        match pos.checked_sub(self.dict.len()) {
+            Some(pos) => self.input[pos],
+            None => self.dict[pos],
+        }
+    }
+
+    pub(super) fn read_u32(&self, pos: usize) -> u32 {
+        let bytes = match pos.checked_sub(self.dict.len()) {
+            Some(pos) => &self.input[pos..pos + 4],
+            None if pos + 4 <= self.dict.len() => &self.dict[pos..pos + 4],
+            // the bytes cross the end of the dictionary
+            None => {
+                return u32::from_le_bytes([
+                    self.byte(pos),
+                    self.byte(pos + 1),
+                    self.byte(pos + 2),
+                    self.byte(pos + 3),
+                ])
+            }
+        };
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    /// Returns the input between the two positions.
+    pub(super) fn slice(&self, from: usize, to: usize) -> &'a [u8] {
+        &self.input[from - self.dict.len()..to - self.dict.len()]
+    }
+
+    /// Counts the number of equal bytes at `a` and `b`, where `a` is in front of `b`
+    /// and the bytes at `b` must end before `limit`.
+    pub(super) fn count_same(&self, a: usize, b: usize, limit: usize) -> usize {
+        let other = self.slice(b, limit);
+        match a.checked_sub(self.dict.len()) {
+            Some(a) => count_same(&self.input[a..], other),
+            None => {
+                let count = count_same(&self.dict[a..], other);
+                // a match that reaches the end of the dictionary continues in the input
+                if a + count == self.dict.len() {
+                    count + count_same(self.input, &other[count..])
+                } else {
+                    count
+                }
+            }
+        }
+    }
+}
+
+/// Counts the number of equal bytes at the start of both slices.
+fn count_same(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+fn push<O: Buf<u8>>(out: &mut O, byte: u8) -> Result<(), CompressError> {
+    match out.push(byte) {
+        None => Ok(()),
+        Some(_) => Err(CompressError::MemoryLimitExceeded),
+    }
+}
+
+/// Writes the additional bytes of a length that didn't fit into the token.
+fn write_int<O: Buf<u8>>(out: &mut O, mut len: usize) -> Result<(), CompressError> {
+    while len >= 255 {
+        push(out, 255)?;
+        len -= 255;
+    }
+    push(out, len as u8)
+}
+
+/// Writes a full sequence, consisting of the literals, followed by a match
+/// that starts `offset` bytes behind and is `len` bytes long.
+pub(super) fn write_sequence<O: Buf<u8>>(
+    out: &mut O,
+    literals: &[u8],
+    offset: u16,
+    len: usize,
+) -> Result<(), CompressError> {
+    debug_assert!(offset != 0 && len >= MIN_MATCH);
+
+    let lit_len = literals.len();
+    let match_len = len - MIN_MATCH;
+    let token = ((lit_len.min(15) as u8) << 4) | match_len.min(15) as u8;
+
+    push(out, token)?;
+    if lit_len >= 15 {
+        write_int(out, lit_len - 15)?;
+    }
+    if !out.extend(literals) {
+        return Err(CompressError::MemoryLimitExceeded);
+    }
+
+    let [low, high] = offset.to_le_bytes();
+    push(out, low)?;
+    push(out, high)?;
+    if match_len >= 15 {
+        write_int(out, match_len - 15)?;
+    }
+    Ok(())
+}
+
+/// Writes the last sequence of a block, which only contains literals.
+pub(super) fn write_literals<O: Buf<u8>>(
+    out: &mut O,
+    literals: &[u8],
+) -> Result<(), CompressError> {
+    if literals.is_empty() {
+        return Ok(());
+    }
+
+    let lit_len = literals.len();
+    push(out, (lit_len.min(15) as u8) << 4)?;
+    if lit_len >= 15 {
+        write_int(out, lit_len - 15)?;
+    }
+    if !out.extend(literals) {
+        return Err(CompressError::MemoryLimitExceeded);
+    }
+    Ok(())
+}
+
+/// Returns the largest offset of all matches inside the compressed `block`.
+#[cfg(test)]
+pub(crate) fn max_offset(block: &[u8]) -> usize {
+    let mut reader = crate::decompress::ByteIter::new(block);
+    let mut max = 0;
+    while let Ok(token) = reader.read_byte() {
+        let literals = reader.read_int((token >> 4) as usize).unwrap();
+        reader.take(literals).unwrap();
+        let offset = match reader.read() {
+            Ok(offset) => u16::from_le_bytes(offset),
+            // the last sequence only contains literals
+            Err(_) => break,
+        };
+        max = max.max(offset as usize);
+        reader.read_int((token & 0xF) as usize).unwrap();
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compressed_bound, decompress_block, decompress_block_with_dict, ArrayBuf, Buf,
+        CompressError, HeapBuf,
+    };
+
+    fn roundtrip(input: &[u8]) -> usize {
+        let mut compressed = HeapBuf::new();
+        super::compress_block(input, &mut compressed).unwrap();
+        assert!(compressed.len() <= compressed_bound(input.len()).unwrap());
+
+        let mut decompressed = HeapBuf::new();
+        decompress_block(compressed.as_slice(), &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), input);
+        compressed.len()
+    }
+
+    #[test]
+    fn block_empty() {
+        assert_eq!(roundtrip(&[]), 0);
+    }
+
+    #[test]
+    fn block_small() {
+        roundtrip(b"a");
+        roundtrip(b"hello");
+        roundtrip(b"aaaaaaaaaaaaa");
+    }
+
+    #[test]
+    fn block_repeated() {
+        let input = [b'a'; 1000];
+        assert!(roundtrip(&input) < 20);
+
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(100);
+        assert!(roundtrip(&input) < 100);
+    }
+
+    #[test]
+    fn block_incompressible() {
+        roundtrip(&crate::test_data(100_000, 1, &[]));
+    }
+
+    #[test]
+    fn block_acceleration() {
+        let input = crate::test_data(64 * 1024, 1, &[100, 3000]);
+        let default = roundtrip(&input);
+
+        for &acceleration in &[0, 1, 2, 8, 64, u32::MAX] {
+            let mut compressed = HeapBuf::new();
+            super::compress_block_fast(&input, &mut compressed, acceleration).unwrap();
+
+            let mut decompressed = HeapBuf::new();
+            decompress_block(compressed.as_slice(), &mut decompressed).unwrap();
+            assert_eq!(decompressed.as_slice(), &input[..]);
+
+            if acceleration <= 1 {
+                assert_eq!(compressed.len(), default);
+            } else {
+                assert!(compressed.len() >= default);
+            }
+        }
+    }
+
+    #[test]
+    fn block_with_dict() {
+        let dict = br#"{"id":0,"name":"","email":"","active":false,"roles":["admin","user"]}"#;
+        let input =
+            br#"{"id":42,"name":"lz4","email":"lz4@example.com","active":true,"roles":["user"]}"#;
+
+        let mut compressed = HeapBuf::new();
+        super::compress_block_with_dict(input, dict, &mut compressed).unwrap();
+
+        let mut decompressed = HeapBuf::new();
+        decompress_block_with_dict(compressed.as_slice(), dict, &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), &input[..]);
+        assert!(compressed.len() < roundtrip(input));
+    }
+
+    #[test]
+    fn block_with_dict_crossing() {
+        // the first match starts inside the dictionary and continues in the input
+        let dict = b"some dictionary content 0123456789";
+        let input = b"0123456789012345678901234567890123456789 and more";
+
+        let mut compressed = HeapBuf::new();
+        super::compress_block_with_dict(input, dict, &mut compressed).unwrap();
+
+        let mut decompressed = HeapBuf::new();
+        decompress_block_with_dict(compressed.as_slice(), dict, &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), &input[..]);
+    }
+
+    #[test]
+    fn block_out_of_memory() {
+        let input = b"The quick brown fox jumps over the lazy dog.";
+        let mut buf = ArrayBuf::<u8, 16>::new();
+        assert!(matches!(
+            super::compress_block(input, &mut buf),
+            Err(CompressError::MemoryLimitExceeded)
+        ));
+    }
+
+    #[test]
+    fn block_max_distance() {
+        // every third chunk is a copy of the data 20,000 bytes in front of it
+        let input = crate::test_data(128 * 1024, 2, &[1000, 20_000]);
+
+        let mut unlimited = HeapBuf::new();
+        super::compress_block(&input, &mut unlimited).unwrap();
+        assert!(super::max_offset(unlimited.as_slice()) > 16 * 1024);
+
+        for &distance in &[1024, 4096, 16 * 1024] {
+            let mut compressed = HeapBuf::new();
+            super::compress_block_with_max_distance(&input, &mut compressed, distance).unwrap();
+            assert!(super::max_offset(compressed.as_slice()) <= distance as usize);
+            assert!(compressed.len() > unlimited.len());
+
+            let mut decompressed = HeapBuf::new();
+            decompress_block(compressed.as_slice(), &mut decompressed).unwrap();
+            assert!(decompressed.as_slice() == &input[..]);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+//! Streaming compression into the LZ4 frame format, using the `std::io` traits.
+
+use super::framed::{write_block, write_header, WINDOW_SIZE};
+use super::raw::Window;
+use super::{CompressError, FramePreferences};
+use crate::{Buf, HeapBuf};
+use core::hash::Hasher;
+use std::io::{self, Write};
+use twox_hash::XxHash32;
+
+/// Compresses everything that is written to it into a single LZ4 frame,
+/// which is written to `W`.
+///
+/// The input is buffered until a block is full, then the block is compressed
+/// and written to the inner writer. The frame is only complete after calling
+/// [`finish`](FrameEncoder::finish), which writes the end mark and the content checksum.
+/// Dropping the encoder without finishing it results in a truncated frame.
+///
+/// If the inner writer fails, the compressed data is kept, so every method can be
+/// called again to continue where the failed call stopped.
+///
+/// Since the size of the content is unknown when writing the header,
+/// [`FramePreferences::content_size`] is ignored.
+///
+/// This struct is only available with the `std` feature enabled.
+///
+/// # Example
+///
+/// ```
+/// use std::io::{self, Write};
+///
+/// let mut encoder = lz4::FrameEncoder::new(Vec::new());
+/// io::copy(&mut &b"hello\n"[..], &mut encoder).unwrap();
+/// encoder.finish().unwrap();
+/// let compressed = encoder.into_inner();
+///
+/// let mut decompressed = lz4::HeapBuf::new();
+/// lz4::decompress(&compressed, &mut decompressed).unwrap();
+/// ```
+pub struct FrameEncoder<W: Write> {
+    writer: W,
+    prefs: FramePreferences,
+    /// The data of the previous blocks that can still be referenced,
+    /// followed by the input of the current block.
+    buffer: Vec<u8>,
+    /// The position inside `buffer` where the input of the current block starts.
+    start: usize,
+    /// The compressed data that wasn't written yet.
+    out: HeapBuf<u8>,
+    /// The number of bytes at the start of `out` that were already written.
+    written: usize,
+    hasher: XxHash32,
+    header_written: bool,
+    /// Whether the end mark was added to `out`.
+    finished: bool,
+}
+
+impl<W: Write> FrameEncoder<W> {
+    /// Creates a new encoder, that writes a frame with the default preferences into `writer`.
+    pub fn new(writer: W) -> Self {
+        Self::with_preferences(writer, FramePreferences::new())
+    }
+
+    /// Creates a new encoder, that writes a frame with the given preferences into `writer`.
+    pub fn with_preferences(writer: W, prefs: FramePreferences) -> Self {
+        Self {
+            writer,
+            prefs: prefs.content_size(false),
+            buffer: Vec::new(),
+            start: 0,
+            out: HeapBuf::new(),
+            written: 0,
+            hasher: XxHash32::with_seed(0),
+            header_written: false,
+            finished: false,
+        }
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer will corrupt the frame.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the underlying writer.
+    ///
+    /// The frame is only complete if [`finish`](Self::finish) succeeded before.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Compresses the remaining input and finishes the frame.
+    ///
+    /// If writing fails, this can be called again to write the rest of the frame.
+    /// Afterwards, the encoder doesn't accept any more input.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.write_pending()?;
+
+            // the end mark
+            self.out.extend(&0u32.to_le_bytes());
+            if self.prefs.content_checksum {
+                self.out
+                    .extend(&(self.hasher.finish() as u32).to_le_bytes());
+            }
+            self.finished = true;
+        }
+
+        self.write_out()?;
+        self.writer.flush()
+    }
+
+    /// Compresses the input of the current block, even if the block isn't full yet,
+    /// and writes the compressed data to the underlying writer.
+    ///
+    /// The compressed data is kept until it was written, so if writing fails,
+    /// the next call continues where this one stopped.
+    fn write_pending(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            write_header(&mut self.out, &self.prefs, None).map_err(invalid)?;
+            self.header_written = true;
+        }
+
+        if self.start < self.buffer.len() {
+            let input = &self.buffer[self.start..];
+            self.hasher.write(input);
+
+            let window = Window::new(&[], &self.buffer);
+            write_block(&mut self.out, window, self.start, None, &self.prefs).map_err(invalid)?;
+
+            // keep only the data that can be referenced by the next block
+            if self.prefs.independent_blocks {
+                self.buffer.clear();
+            } else if self.buffer.len() > WINDOW_SIZE {
+                self.buffer.drain(..self.buffer.len() - WINDOW_SIZE);
+            }
+            self.start = self.buffer.len();
+        }
+
+        self.write_out()
+    }
+
+    /// Writes the compressed data that wasn't written yet to the underlying writer.
+    fn write_out(&mut self) -> io::Result<()> {
+        while self.written < self.out.len() {
+            match self.writer.write(&self.out.as_slice()[self.written..]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the compressed data",
+                    ))
+                }
+                Ok(count) => self.written += count,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.out.truncate(0);
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for FrameEncoder<W> {
+    /// Collects the input of the current block.
+    ///
+    /// A full block is only compressed and written by the next call, so if that fails,
+    /// none of `buf` was consumed and the call can be retried with the same input.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the frame is already finished",
+            ));
+        }
+
+        let block_size = self.prefs.block_size.size();
+        if self.buffer.len() - self.start == block_size {
+            self.write_pending()?;
+        }
+
+        let len = buf.len().min(block_size - (self.buffer.len() - self.start));
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Compresses the input that is buffered at the moment into a block,
+    /// and flushes the underlying writer.
+    ///
+    /// Flushing often results in small blocks, which worsens the compression ratio.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.writer.flush()
+    }
+}
+
+fn invalid(err: CompressError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameEncoder;
+    use crate::{decompress, BlockSize, Buf, CompressionLevel, FramePreferences, HeapBuf};
+    use std::io::{self, Write};
+
+    fn roundtrip(input: &[u8], prefs: FramePreferences, chunk: usize) -> Vec<u8> {
+        let mut encoder = FrameEncoder::with_preferences(Vec::new(), prefs);
+        for chunk in input.chunks(chunk) {
+            encoder.write_all(chunk).unwrap();
+        }
+        encoder.finish().unwrap();
+        let compressed = encoder.into_inner();
+
+        let mut decompressed = HeapBuf::new();
+        decompress(&compressed, &mut decompressed).unwrap();
+        assert!(decompressed.as_slice() == input);
+        compressed
+    }
+
+    #[test]
+    fn encoder_empty() {
+        let compressed = roundtrip(&[], FramePreferences::new(), 1);
+        let mut expected = HeapBuf::new();
+        crate::compress(&[], &mut expected).unwrap();
+        assert_eq!(compressed, expected.as_slice());
+    }
+
+    #[test]
+    fn encoder_matches_compress() {
+        let input = crate::test_data(440 * 1024, 1, &[100, 3000, 20_000]);
+        for &independent_blocks in &[true, false] {
+            let prefs = FramePreferences::new()
+                .independent_blocks(independent_blocks)
+                .block_checksums(true);
+
+            let mut expected = HeapBuf::new();
+            crate::compress_with(&input, &mut expected, &prefs).unwrap();
+            for &chunk in &[1000, 64 * 1024, 100_000] {
+                assert!(roundtrip(&input, prefs, chunk) == expected.as_slice());
+            }
+        }
+    }
+
+    #[test]
+    fn encoder_preferences() {
+        let input = crate::test_data(640 * 1024, 2, &[100, 3000, 20_000]);
+        let prefs = FramePreferences::new()
+            .compression_level(CompressionLevel::new(10).unwrap())
+            .block_size(BlockSize::Max256KB)
+            .independent_blocks(false)
+            .content_checksum(false)
+            .content_size(true);
+        roundtrip(&input, prefs, 7777);
+    }
+
+    #[test]
+    fn encoder_flush() {
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(b"hello ").unwrap();
+        encoder.flush().unwrap();
+        assert!(encoder.get_ref().len() > 7);
+        encoder.write_all(b"world").unwrap();
+        encoder.finish().unwrap();
+        let compressed = encoder.into_inner();
+
+        let mut decompressed = HeapBuf::new();
+        decompress(&compressed, &mut decompressed).unwrap();
+        assert_eq!(decompressed.as_slice(), b"hello world");
+    }
+
+    /// A writer that writes at most 1000 bytes at once and fails every third call.
+    struct Flaky {
+        data: Vec<u8>,
+        calls: usize,
+        kind: io::ErrorKind,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls == 3 {
+                self.calls = 0;
+                return Err(io::Error::new(self.kind, "flaky"));
+            }
+            let len = buf.len().min(1000);
+            self.data.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn encoder_failing_writer() {
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(10_000);
+        let prefs = FramePreferences::new()
+            .block_size(BlockSize::Max64KB)
+            .independent_blocks(false);
+        let mut expected = HeapBuf::new();
+        crate::compress_with(&input, &mut expected, &prefs).unwrap();
+
+        // `write_all` retries interrupted writes by itself
+        let writer = Flaky {
+            data: Vec::new(),
+            calls: 0,
+            kind: io::ErrorKind::Interrupted,
+        };
+        let mut encoder = FrameEncoder::with_preferences(writer, prefs);
+        encoder.write_all(&input).unwrap();
+        encoder.finish().unwrap();
+        assert!(encoder.into_inner().data == expected.as_slice());
+
+        // other errors are returned, and the same input is written again
+        let writer = Flaky {
+            data: Vec::new(),
+            calls: 0,
+            kind: io::ErrorKind::Other,
+        };
+        let mut encoder = FrameEncoder::with_preferences(writer, prefs);
+        let mut rest = &input[..];
+        let mut errors = 0;
+        while !rest.is_empty() {
+            match encoder.write(&rest[..rest.len().min(30_000)]) {
+                Ok(len) => rest = &rest[len..],
+                Err(err) => {
+                    assert_eq!(err.kind(), io::ErrorKind::Other);
+                    errors += 1;
+                }
+            }
+        }
+        while let Err(err) = encoder.flush() {
+            assert_eq!(err.kind(), io::ErrorKind::Other);
+            errors += 1;
+        }
+        assert!(errors > 0);
+
+        // writing the end mark fails, but `finish` can be retried
+        encoder.get_mut().calls = 2;
+        assert_eq!(encoder.finish().unwrap_err().kind(), io::ErrorKind::Other);
+        while let Err(err) = encoder.finish() {
+            assert_eq!(err.kind(), io::ErrorKind::Other);
+        }
+        assert!(encoder.write(b"more").is_err());
+        assert!(encoder.into_inner().data == expected.as_slice());
+    }
+
+    #[test]
+    fn encoder_copy() {
+        let input = crate::test_data(520 * 1024, 3, &[100, 3000, 20_000]);
+        let mut encoder = FrameEncoder::new(Vec::new());
+        io::copy(&mut &input[..], &mut encoder).unwrap();
+        encoder.finish().unwrap();
+        let compressed = encoder.into_inner();
+
+        let mut decompressed = Vec::new();
+        io::copy(
+            &mut crate::FrameDecoder::new(&compressed[..]),
+            &mut decompressed,
+        )
+        .unwrap();
+        assert!(decompressed == input);
+    }
+}
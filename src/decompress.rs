@@ -7,19 +7,43 @@ pub(crate) use iter::ByteIter;
 
 mod framed;
 pub use framed::*;
+pub(crate) use framed::{Flags, UNCOMPRESSED_DATA};
+
+mod legacy;
+pub use legacy::*;
+pub(crate) use legacy::{LEGACY_BLOCK_SIZE, LEGACY_MAGIC};
+
+mod push;
+pub use push::*;
 
 mod raw;
 pub use raw::*;
 
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+pub use stream::*;
+
 /// The magic number which is at the start of every
 /// compressed data in the frame format.
-const MAGIC: u32 = 0x184D2204;
+pub(crate) const MAGIC: u32 = 0x184D2204;
+
+/// The magic number of the first skippable frame. The 16 magic numbers
+/// starting at this one all mark skippable frames.
+pub(crate) const SKIPPABLE_MAGIC: u32 = 0x184D2A50;
+
+/// Returns `true` if `magic` is the magic number of a skippable frame.
+pub(crate) const fn is_skippable(magic: u32) -> bool {
+    magic & !0xF == SKIPPABLE_MAGIC
+}
 
 /// The version this decompresser is capable of decompressing.
-const VERSION: u8 = 0b01;
+pub(crate) const VERSION: u8 = 0b01;
 
 /// The error type that is returned by various decompression-related methods.
-#[derive(Clone, Copy, Debug)]
+///
+/// Methods that decompress frames wrap it inside of a [`FrameError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum DecompressError {
     /// Inidicates that the `out` pointer didn't contain enough memory
@@ -32,6 +56,11 @@ pub enum DecompressError {
     ///
     /// This is most likely caused by trying to decompress invalid input.
     ZeroMatchOffset,
+    /// The offset of a match points in front of the start of the output,
+    /// and in front of the dictionary, if there is one.
+    ///
+    /// This is most likely caused by trying to decompress invalid input.
+    OffsetOutOfBounds,
 
     /// The data that was tried to decompress, started with an invalid magic number.
     ///
@@ -54,45 +83,183 @@ pub enum DecompressError {
     /// number for the maximum block size.
     InvalidMaxBlockSize,
     /// The checksum check for the frame header failed.
-    HeaderChecksumInvalid,
+    HeaderChecksumInvalid {
+        /// The checksum stored in the frame header.
+        expected: u8,
+        /// The checksum of the frame header.
+        actual: u8,
+    },
     /// The checksum check for a block failed.
-    BlockChecksumInvalid,
+    BlockChecksumInvalid {
+        /// The checksum stored behind the block.
+        expected: u32,
+        /// The checksum of the block.
+        actual: u32,
+    },
     /// The checksum check for the decompressed content failed.
-    ContentChecksumInvalid,
+    ContentChecksumInvalid {
+        /// The checksum stored at the end of the frame.
+        expected: u32,
+        /// The checksum of the decompressed content.
+        actual: u32,
+    },
     /// The content size that was provided in the frame header doesn't
     /// match the actual output size.
-    ContentSizeInvalid,
+    ContentSizeInvalid {
+        /// The content size stored in the frame header.
+        expected: u64,
+        /// The size of the decompressed content.
+        actual: u64,
+    },
+    /// The frame requires a dictionary, whose ID is unknown.
+    UnknownDictionary,
+    /// A block, or its decompressed data, is larger than the maximum block size.
+    ///
+    /// This is most likely caused by trying to decompress invalid input.
+    BlockTooLarge,
+    /// The frame exceeds one of the limits of the [`DecompressOptions`].
+    LimitExceeded,
+    /// The frame doesn't contain a content checksum, but
+    /// [`DecompressOptions::require_content_checksum`] is enabled.
+    ContentChecksumMissing,
 }
 
 impl fmt::Display for DecompressError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            DecompressError::MemoryLimitExceeded => f.write_str("not enough memory available in out pointer"),
+            DecompressError::MemoryLimitExceeded => f.write_str("Not enough memory available in out pointer"),
             DecompressError::UnexpectedEof => {
-                f.write_str("expected at lelast one byte to be read, but instead end was reached")
+                f.write_str("Expected at least one byte to be read, but instead end was reached")
             }
             DecompressError::ZeroMatchOffset => f.write_str(
-                "The offset was zero. This is most likely caused by trying to parse invalid input.",
+                "The offset was zero. This is most likely caused by trying to parse invalid input",
+            ),
+            DecompressError::OffsetOutOfBounds => f.write_str(
+                "The offset points outside of the output. This is most likely caused by trying to parse invalid input",
             ),
 
             DecompressError::InvalidMagic => f.write_str(
-                "The magic number is invalid. This is most likely caused by trying to parse invalid input.",
+                "The magic number is invalid. This is most likely caused by trying to parse invalid input",
             ),
-            DecompressError::VersionNotSupported => f.write_str("The data was comrpessed using a version of LZ4 that is not supported."),
-            DecompressError::InvalidInput => f.write_str("The provided data is invalid."),
-            DecompressError::ReservedBitHigh => f.write_str("One of the reserved bits was 1."),
+            DecompressError::VersionNotSupported => f.write_str("The data was compressed using a version of LZ4 that is not supported"),
+            DecompressError::InvalidInput => f.write_str("The provided data is invalid"),
+            DecompressError::ReservedBitHigh => f.write_str("One of the reserved bits was 1"),
             DecompressError::InvalidMaxBlockSize => f.write_str("Maximum block size is invalid"),
-            DecompressError::HeaderChecksumInvalid => f.write_str("Frame header checksum verification failed."),
-            DecompressError::BlockChecksumInvalid => f.write_str("Block checksum verification failed."),
-            DecompressError::ContentChecksumInvalid => f.write_str("Content checksum verification failed."),
-            DecompressError::ContentSizeInvalid => f.write_str("Content size verification failed."),
+            DecompressError::HeaderChecksumInvalid { expected, actual } => write!(
+                f,
+                "Frame header checksum verification failed (expected {:#04x}, got {:#04x})",
+                expected, actual
+            ),
+            DecompressError::BlockChecksumInvalid { expected, actual } => write!(
+                f,
+                "Block checksum verification failed (expected {:#010x}, got {:#010x})",
+                expected, actual
+            ),
+            DecompressError::ContentChecksumInvalid { expected, actual } => write!(
+                f,
+                "Content checksum verification failed (expected {:#010x}, got {:#010x})",
+                expected, actual
+            ),
+            DecompressError::ContentSizeInvalid { expected, actual } => write!(
+                f,
+                "Content size verification failed (expected {}, got {})",
+                expected, actual
+            ),
+            DecompressError::UnknownDictionary => f.write_str("The dictionary required by the frame is unknown"),
+            DecompressError::BlockTooLarge => f.write_str("A block is larger than the maximum block size"),
+            DecompressError::LimitExceeded => f.write_str("The frame exceeds a limit of the decompression options"),
+            DecompressError::ContentChecksumMissing => f.write_str("The frame doesn't contain a content checksum"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecompressError {}
+
+/// The error type that is returned by the methods, which decompress frames.
+///
+/// In addition to the [`DecompressError`] itself, it contains the position in the input
+/// and the index of the block, where the error was detected.
+///
+/// These methods used to return a [`DecompressError`] directly. Since it implements
+/// `From<FrameError>`, the `?` operator still works in functions that return one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FrameError {
+    /// The kind of error.
+    pub kind: DecompressError,
+    /// The number of input bytes that were read before the error was detected,
+    /// so this points right behind the invalid part of the input.
+    pub position: u64,
+    /// The index of the block inside the frame, that caused the error,
+    /// or `None` if the error isn't related to a single block.
+    pub block: Option<u64>,
+}
+
+impl FrameError {
+    pub(crate) const fn new(kind: DecompressError, position: u64, block: Option<u64>) -> Self {
+        Self {
+            kind,
+            position,
+            block,
+        }
+    }
+
+    /// Moves the position `by` bytes further, if the input was part of a larger input.
+    pub(crate) const fn offset(mut self, by: u64) -> Self {
+        self.position += by;
+        self
+    }
+}
+
+impl From<FrameError> for DecompressError {
+    fn from(err: FrameError) -> Self {
+        err.kind
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at input position {}", self.kind, self.position)?;
+        if let Some(block) = self.block {
+            write!(f, ", in block {}", block)?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.kind)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{ArrayBuf, Buf};
+    use crate::{ArrayBuf, Buf, DecompressError, HeapBuf};
+    use alloc::collections::BTreeMap;
+    use core::hash::Hasher;
+    use twox_hash::XxHash32;
+
+    /// Builds a frame with the given dictionary id, that contains a single
+    /// block which references the first 11 bytes of the dictionary.
+    fn dict_frame(id: u32) -> Vec<u8> {
+        let mut frame = super::MAGIC.to_le_bytes().to_vec();
+
+        let mut descriptor = vec![0b0110_0001, 0b0100_0000];
+        descriptor.extend_from_slice(&id.to_le_bytes());
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(&descriptor);
+        frame.extend_from_slice(&descriptor);
+        frame.push((hasher.finish() >> 8) as u8);
+
+        let block = [0x07, 13, 0, 0x10, b'!'];
+        frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&block);
+        frame.extend_from_slice(&0u32.to_le_bytes());
+        frame
+    }
 
     #[test]
     fn hello() {
@@ -103,4 +270,322 @@ mod tests {
         super::decompress(&raw, &mut buf).unwrap();
         assert_eq!(core::str::from_utf8(buf.as_slice()), Ok("hello\n"));
     }
+
+    #[test]
+    fn dictionary_id() {
+        let dict: &[u8] = b"hello world\n\n";
+        let frame = dict_frame(42);
+
+        let mut buf = HeapBuf::new();
+        super::decompress_with_dictionaries(&frame, &mut buf, &[(1, &b""[..]), (42, dict)])
+            .unwrap();
+        assert_eq!(buf.as_slice(), b"hello world!");
+
+        let mut dicts = BTreeMap::new();
+        dicts.insert(42, dict.to_vec());
+        let mut buf = HeapBuf::new();
+        super::decompress_with_dictionaries(&frame, &mut buf, &dicts).unwrap();
+        assert_eq!(buf.as_slice(), b"hello world!");
+    }
+
+    #[test]
+    fn dictionary_id_unknown() {
+        let frame = dict_frame(42);
+
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress(&frame, &mut buf).map_err(|err| err.kind),
+            Err(DecompressError::UnknownDictionary)
+        ));
+
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_with_dictionaries(&frame, &mut buf, &[(1, &b"hello"[..])])
+                .map_err(|err| err.kind),
+            Err(DecompressError::UnknownDictionary)
+        ));
+    }
+
+    #[test]
+    fn dictionary_id_checksum() {
+        let mut frame = dict_frame(42);
+        // the dictionary id is part of the header checksum
+        frame[6] = 43;
+
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress(&frame, &mut buf).map_err(|err| err.kind),
+            Err(DecompressError::HeaderChecksumInvalid { .. })
+        ));
+    }
+
+    #[test]
+    fn skippable() {
+        let mut input = HeapBuf::new();
+        crate::write_skippable_frame(1, b"first", &mut input).unwrap();
+        crate::write_skippable_frame(14, b"second", &mut input).unwrap();
+        crate::compress(b"hello", &mut input).unwrap();
+
+        let mut frames = Vec::new();
+        let mut buf = HeapBuf::new();
+        super::decompress_with_skippable(input.as_slice(), &mut buf, |frame| {
+            frames.push((frame.index, frame.data.to_vec()))
+        })
+        .unwrap();
+        assert_eq!(frames, [(1, b"first".to_vec()), (14, b"second".to_vec())]);
+        assert_eq!(buf.as_slice(), b"hello");
+
+        let mut buf = HeapBuf::new();
+        super::decompress(input.as_slice(), &mut buf).unwrap();
+        assert_eq!(buf.as_slice(), b"hello");
+    }
+
+    #[test]
+    fn skippable_truncated() {
+        let mut input = HeapBuf::new();
+        crate::write_skippable_frame(0, b"metadata", &mut input).unwrap();
+
+        for len in 4..input.len() {
+            let mut buf = HeapBuf::new();
+            assert!(matches!(
+                super::decompress_with_skippable(&input.as_slice()[..len], &mut buf, |_| {})
+                    .map_err(|err| err.kind),
+                Err(DecompressError::UnexpectedEof)
+            ));
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let mut input = HeapBuf::new();
+        crate::compress(b"hello ", &mut input).unwrap();
+        crate::write_skippable_frame(2, b"metadata", &mut input).unwrap();
+        let prefs = crate::FramePreferences::new()
+            .block_checksums(true)
+            .content_size(true);
+        crate::compress_with(&b"world".repeat(100_000), &mut input, &prefs).unwrap();
+        crate::compress(&[], &mut input).unwrap();
+
+        let frames = super::FrameIter::new(input.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            frames.iter().map(|f| f.len()).sum::<usize>(),
+            input.len() - 16
+        );
+
+        let mut buf = HeapBuf::new();
+        super::decompress_frames(input.as_slice(), &mut buf).unwrap();
+        assert!(buf.as_slice()[..6] == b"hello "[..]);
+        assert!(buf.as_slice()[6..] == b"world".repeat(100_000)[..]);
+
+        let mut buf = HeapBuf::new();
+        super::decompress_frames(&[], &mut buf).unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn frames_trailing_data() {
+        let mut input = HeapBuf::new();
+        crate::compress(b"hello", &mut input).unwrap();
+        let len = input.len();
+        input.extend(b"garbage");
+
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_frames(input.as_slice(), &mut buf).map_err(|err| err.kind),
+            Err(DecompressError::InvalidMagic)
+        ));
+
+        let mut frames = super::FrameIter::new(input.as_slice());
+        assert_eq!(frames.next().unwrap().unwrap().len(), len);
+        assert!(frames.next().unwrap().is_err());
+        assert!(frames.next().is_none());
+
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_frames(&input.as_slice()[..len - 1], &mut buf)
+                .map_err(|err| err.kind),
+            Err(DecompressError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn frames_limits() {
+        let mut input = HeapBuf::new();
+        for _ in 0..3 {
+            crate::compress(&[b'x'; 4000], &mut input).unwrap();
+        }
+
+        // the limit applies to all frames, but not to the data `out` already contains
+        let options = super::DecompressOptions::new().max_output(10_000);
+        let mut buf = HeapBuf::new();
+        buf.extend(b"prefix");
+        let err = super::decompress_frames_with(input.as_slice(), &mut buf, &options).unwrap_err();
+        assert_eq!(err.kind, DecompressError::LimitExceeded);
+        assert!(buf.len() <= 6 + 10_000);
+
+        let options = super::DecompressOptions::new().max_output(12_000);
+        let mut buf = HeapBuf::new();
+        buf.extend(b"prefix");
+        super::decompress_frames_with(input.as_slice(), &mut buf, &options).unwrap();
+        assert_eq!(buf.len(), 6 + 12_000);
+
+        // frames with a dictionary
+        let frame = dict_frame(42);
+        let dicts = [(42, &b"hello world\n\n"[..])];
+        let options = super::DecompressOptions::new().max_output(10);
+        let mut buf = HeapBuf::new();
+        let err =
+            super::decompress_with_dictionaries_and_options(&frame, &mut buf, &dicts, &options)
+                .unwrap_err();
+        assert_eq!(err.kind, DecompressError::LimitExceeded);
+        assert!(buf.len() <= 10);
+
+        let options = super::DecompressOptions::new().max_output(12);
+        let mut buf = HeapBuf::new();
+        super::decompress_with_dictionaries_and_options(&frame, &mut buf, &dicts, &options)
+            .unwrap();
+        assert_eq!(buf.as_slice(), b"hello world!");
+    }
+
+    #[test]
+    fn frame_info() {
+        let mut frame = dict_frame(42);
+        let info = super::frame_info(&frame).unwrap();
+        assert_eq!(info.version, 1);
+        assert!(info.independent_blocks);
+        assert!(!info.block_checksums);
+        assert!(!info.content_checksum);
+        assert_eq!(info.block_size, crate::BlockSize::Max64KB);
+        assert_eq!(info.content_size, None);
+        assert_eq!(info.dictionary_id, Some(42));
+
+        // the header is enough, the blocks aren't needed
+        let mut input = HeapBuf::new();
+        crate::write_skippable_frame(0, b"metadata", &mut input).unwrap();
+        input.extend(&frame[..11]);
+        assert_eq!(super::frame_info(input.as_slice()).unwrap(), info);
+
+        assert!(matches!(
+            super::frame_info(&frame[..10]).map_err(|err| err.kind),
+            Err(DecompressError::UnexpectedEof)
+        ));
+        frame[0] = 0;
+        assert!(matches!(
+            super::frame_info(&frame).map_err(|err| err.kind),
+            Err(DecompressError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn non_empty_output() {
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(3000);
+        let prefs = crate::FramePreferences::new()
+            .independent_blocks(false)
+            .content_size(true);
+        let mut frame = HeapBuf::new();
+        crate::compress_with(&input, &mut frame, &prefs).unwrap();
+
+        // a prefix, that looks like the content, must not be referenced by the frame
+        let mut buf = HeapBuf::new();
+        buf.extend(&input[..1000]);
+        super::decompress(frame.as_slice(), &mut buf).unwrap();
+        assert!(buf.as_slice()[..1000] == input[..1000]);
+        assert!(buf.as_slice()[1000..] == input[..]);
+
+        // reusing the buffer for the next frame
+        super::decompress(frame.as_slice(), &mut buf).unwrap();
+        assert_eq!(buf.len(), 1000 + 2 * input.len());
+        assert!(buf.as_slice()[1000 + input.len()..] == input[..]);
+
+        let mut buf = ArrayBuf::<u8, 16>::new();
+        buf.extend(b"hello ");
+        let mut frame = HeapBuf::new();
+        crate::compress_with(b"world", &mut frame, &prefs).unwrap();
+        super::decompress(frame.as_slice(), &mut buf).unwrap();
+        assert_eq!(buf.as_slice(), b"hello world");
+    }
+
+    #[test]
+    fn error_context() {
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(5000);
+        let prefs = crate::FramePreferences::new().block_checksums(true);
+        let mut frame = HeapBuf::new();
+        crate::compress_with(&input, &mut frame, &prefs).unwrap();
+        let mut frame = frame.as_slice().to_vec();
+
+        // walk to the checksum of the third block and corrupt it
+        let mut pos = 7;
+        for _ in 0..3 {
+            let size =
+                u32::from_le_bytes([frame[pos], frame[pos + 1], frame[pos + 2], frame[pos + 3]]);
+            pos += 4 + (size & 0x7FFF_FFFF) as usize + 4;
+        }
+        frame[pos - 1] ^= 0xFF;
+
+        let mut buf = HeapBuf::new();
+        let err = super::decompress(&frame, &mut buf).unwrap_err();
+        assert_eq!(err.block, Some(2));
+        assert_eq!(err.position, pos as u64);
+        match err.kind {
+            DecompressError::BlockChecksumInvalid { expected, actual } => {
+                assert_eq!(expected ^ actual, 0xFF00_0000);
+                assert_eq!(
+                    err.to_string(),
+                    format!(
+                        "Block checksum verification failed (expected {:#010x}, got {:#010x}) \
+                         at input position {}, in block 2",
+                        expected, actual, pos
+                    )
+                );
+            }
+            kind => panic!("unexpected error: {}", kind),
+        }
+
+        // the position counts from the start of all frames
+        let mut frames = HeapBuf::new();
+        crate::compress(b"hello", &mut frames).unwrap();
+        let offset = frames.len();
+        frames.extend(&frame);
+        let mut buf = HeapBuf::new();
+        let err = super::decompress_frames(frames.as_slice(), &mut buf).unwrap_err();
+        assert_eq!(err.position, (offset + pos) as u64);
+        assert_eq!(err.block, Some(2));
+    }
+
+    #[test]
+    fn error_context_content() {
+        let prefs = crate::FramePreferences::new().content_size(true);
+        let mut frame = HeapBuf::new();
+        crate::compress_with(b"hello", &mut frame, &prefs).unwrap();
+        let mut frame = frame.as_slice().to_vec();
+
+        // change the content size and fix the header checksum
+        frame[6] = 6;
+        let mut hasher = XxHash32::with_seed(0);
+        hasher.write(&frame[4..14]);
+        let expected = frame[14];
+        frame[14] = (hasher.finish() >> 8) as u8;
+
+        let mut buf = HeapBuf::new();
+        let err = super::decompress(&frame, &mut buf).unwrap_err();
+        assert_eq!(
+            err.kind,
+            DecompressError::ContentSizeInvalid {
+                expected: 6,
+                actual: 5
+            }
+        );
+        assert_eq!(err.block, None);
+
+        frame[14] = expected;
+        let err = super::frame_info(&frame).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            DecompressError::HeaderChecksumInvalid { .. }
+        ));
+        assert_eq!(err.position, 15);
+    }
 }
@@ -4,17 +4,17 @@
 
 #![allow(non_upper_case_globals)]
 
-use super::{ByteIter, DecompressError};
-use crate::Buf;
+use super::{ByteIter, DecompressError, FrameError, PushDecoder};
+use crate::{BlockSize, Buf};
 use bitflags::bitflags;
 use core::hash::Hasher;
 use twox_hash::XxHash32;
 
 /// The highest bit indicates if data is compressed or uncompressed.
-const UNCOMPRESSED_DATA: u32 = 1 << 31;
+pub(crate) const UNCOMPRESSED_DATA: u32 = 1 << 31;
 
 bitflags! {
-    struct Flags: u8 {
+    pub(crate) struct Flags: u8 {
         const IndependentBlocks = 0b00100000;
         const BlockChecksums    = 0b00010000;
         const ContentSize       = 0b00001000;
@@ -40,132 +40,664 @@ fn parse_flags(raw: u8) -> Result<Flags, DecompressError> {
     Ok(Flags::from_bits_truncate(raw))
 }
 
-/// This method can be used to decompress data that is compressed using
-/// the LZ4 [Frame Format].
+/// The frame descriptor, which follows the magic number of every frame.
+#[derive(Clone, Copy)]
+pub(crate) struct FrameHeader {
+    pub(crate) flags: Flags,
+    pub(crate) block_size: BlockSize,
+    pub(crate) content_size: Option<u64>,
+    pub(crate) dict_id: Option<u32>,
+}
+
+impl FrameHeader {
+    /// Returns the length of the frame descriptor, that starts with the `flags` byte.
+    pub(crate) fn len(flags: u8) -> usize {
+        let flags = Flags::from_bits_truncate(flags);
+        let mut len = 3;
+        if flags.contains(Flags::ContentSize) {
+            len += 8;
+        }
+        if flags.contains(Flags::DictionaryId) {
+            len += 4;
+        }
+        len
+    }
+
+    /// Parses and verifies the frame descriptor.
+    pub(crate) fn parse(reader: &mut ByteIter<'_>) -> Result<Self, DecompressError> {
+        let mut hasher = XxHash32::with_seed(0);
+
+        let flags = reader.read_byte()?;
+        hasher.write_u8(flags);
+        let flags = parse_flags(flags)?;
+
+        let block_descriptor = reader.read_byte()?;
+        hasher.write_u8(block_descriptor);
+        // check if all reserved bits are zero
+        if (block_descriptor & 0b10001111) != 0 {
+            return Err(DecompressError::ReservedBitHigh);
+        }
+
+        let block_size = BlockSize::from_id((block_descriptor >> 4) & 0b111)
+            .ok_or(DecompressError::InvalidMaxBlockSize)?;
+
+        let content_size = if flags.contains(Flags::ContentSize) {
+            let size = u64::from_le_bytes(reader.read()?);
+            hasher.write_u64(size);
+            Some(size)
+        } else {
+            None
+        };
+
+        let dict_id = if flags.contains(Flags::DictionaryId) {
+            let id = u32::from_le_bytes(reader.read()?);
+            hasher.write_u32(id);
+            Some(id)
+        } else {
+            None
+        };
+
+        let expected = reader.read_byte()?;
+        let actual = (hasher.finish() >> 8) as u8;
+        if expected != actual {
+            return Err(DecompressError::HeaderChecksumInvalid { expected, actual });
+        }
+
+        Ok(Self {
+            flags,
+            block_size,
+            content_size,
+            dict_id,
+        })
+    }
+
+    pub(crate) fn info(&self) -> FrameInfo {
+        FrameInfo {
+            version: super::VERSION,
+            independent_blocks: self.flags.contains(Flags::IndependentBlocks),
+            block_checksums: self.flags.contains(Flags::BlockChecksums),
+            content_checksum: self.flags.contains(Flags::ContentChecksum),
+            block_size: self.block_size,
+            content_size: self.content_size,
+            dictionary_id: self.dict_id,
+        }
+    }
+}
+
+/// The information inside the header of a frame.
 ///
-/// If you want a streaming decompresser, you have to enable `std` feature
-/// and use [`stream::Decompresser`](crate::decompress::stream::Decompressor).
+/// This can be read without decompressing anything by using [`frame_info`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FrameInfo {
+    /// The version of the frame format, which is always `1`.
+    pub version: u8,
+    /// `true` if the blocks can be decompressed independently of each other,
+    /// `false` if they reference the data of the previous blocks.
+    pub independent_blocks: bool,
+    /// `true` if every block is followed by a checksum of its compressed data.
+    pub block_checksums: bool,
+    /// `true` if the frame ends with a checksum of the uncompressed content.
+    pub content_checksum: bool,
+    /// The maximum size of the uncompressed data inside of a block.
+    pub block_size: BlockSize,
+    /// The size of the uncompressed content, if it's stored in the header.
+    pub content_size: Option<u64>,
+    /// The ID of the dictionary that is required to decompress the frame.
+    ///
+    /// See [`decompress_with_dictionaries`] for more information.
+    pub dictionary_id: Option<u32>,
+}
+
+/// Reads the header of the frame at the start of `input`, without decompressing anything.
 ///
-/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
-pub fn decompress<B: Buf<u8>>(input: &[u8], out: &mut B) -> Result<(), DecompressError> {
+/// Only the magic number and the frame descriptor have to be part of `input`.
+/// [Skippable frames](SkippableFrame) in front of the frame are skipped.
+/// This can be used to allocate enough memory for the uncompressed content,
+/// if the [content size](FrameInfo::content_size) is known.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{BlockSize, Buf, FramePreferences, HeapBuf};
+///
+/// let prefs = FramePreferences::new()
+///     .block_size(BlockSize::Max1MB)
+///     .content_size(true);
+/// let mut frame = HeapBuf::new();
+/// lz4::compress_with(b"hello", &mut frame, &prefs).unwrap();
+///
+/// let info = lz4::frame_info(frame.as_slice()).unwrap();
+/// assert_eq!(info.block_size, BlockSize::Max1MB);
+/// assert_eq!(info.content_size, Some(5));
+/// ```
+pub fn frame_info(input: &[u8]) -> Result<FrameInfo, FrameError> {
+    let (skipped, input) = skip_skippable(input)?;
+
     let mut reader = ByteIter::new(input);
+    let header = read_header(&mut reader)
+        .map_err(|err| FrameError::new(err, (skipped + reader.pos()) as u64, None))?;
+    Ok(header.info())
+}
 
-    let magic = u32::from_le_bytes(reader.read()?);
-    if magic != super::MAGIC {
+/// Reads the magic number and the frame descriptor.
+fn read_header(reader: &mut ByteIter<'_>) -> Result<FrameHeader, DecompressError> {
+    if u32::from_le_bytes(reader.read()?) != super::MAGIC {
         return Err(DecompressError::InvalidMagic);
     }
+    FrameHeader::parse(reader)
+}
 
-    let mut hasher = XxHash32::with_seed(0);
+/// Removes the skippable frames at the start of `input`, and
+/// returns the number of removed bytes together with the rest of `input`.
+fn skip_skippable(mut input: &[u8]) -> Result<(usize, &[u8]), FrameError> {
+    let len = input.len();
+    while let Some((_, rest)) =
+        SkippableFrame::parse(input).map_err(|err| err.offset((len - input.len()) as u64))?
+    {
+        input = rest;
+    }
+    Ok((len - input.len(), input))
+}
 
-    let flags = reader.read_byte()?;
-    hasher.write_u8(flags);
-    let flags = parse_flags(flags)?;
+/// A collection of dictionaries, which are identified by their ID.
+///
+/// This is used to find the dictionary for frames that contain a dictionary ID.
+pub trait Dictionaries {
+    /// Returns the dictionary with the given `id`, or `None` if it is unknown.
+    fn get(&self, id: u32) -> Option<&[u8]>;
+}
 
-    let block_descriptor = reader.read_byte()?;
-    hasher.write_u8(block_descriptor);
-    // check if all reserved bits are zero
-    if (block_descriptor & 0b10001111) != 0 {
-        return Err(DecompressError::ReservedBitHigh);
+/// No dictionaries at all.
+impl Dictionaries for () {
+    fn get(&self, _: u32) -> Option<&[u8]> {
+        None
     }
+}
 
-    let max_block_size = ((block_descriptor >> 4) & 0b111) as usize;
-    let max_block_size = match max_block_size {
-        4..=7 => 1 << (max_block_size * 2 + 8),
-        _ => return Err(DecompressError::InvalidMaxBlockSize),
-    };
+impl Dictionaries for [(u32, &[u8])] {
+    fn get(&self, id: u32) -> Option<&[u8]> {
+        self.iter()
+            .find(|(dict_id, _)| *dict_id == id)
+            .map(|(_, dict)| *dict)
+    }
+}
 
-    let content_size = if flags.contains(Flags::ContentSize) {
-        let size = u64::from_le_bytes(reader.read()?);
-        hasher.write_u64(size);
-        Some(size)
-    } else {
-        None
-    };
+impl<const N: usize> Dictionaries for [(u32, &[u8]); N] {
+    fn get(&self, id: u32) -> Option<&[u8]> {
+        Dictionaries::get(&self[..], id)
+    }
+}
 
-    assert!(
-        !flags.contains(Flags::DictionaryId),
-        "Dictionary IDs are currently not supported"
-    );
+impl<D: Dictionaries + ?Sized> Dictionaries for &D {
+    fn get(&self, id: u32) -> Option<&[u8]> {
+        (**self).get(id)
+    }
+}
 
-    let header_checksum = reader.read_byte()?;
-    let actual_hash = (hasher.finish() >> 8) as u8;
-    if header_checksum != actual_hash {
-        return Err(DecompressError::HeaderChecksumInvalid);
+#[cfg(any(feature = "alloc", test))]
+impl<T: AsRef<[u8]>> Dictionaries for alloc::collections::BTreeMap<u32, T> {
+    fn get(&self, id: u32) -> Option<&[u8]> {
+        alloc::collections::BTreeMap::get(self, &id).map(AsRef::as_ref)
     }
+}
 
-    loop {
-        let size = u32::from_le_bytes(reader.read()?);
+/// A skippable frame, which contains arbitrary data instead of compressed data.
+///
+/// Skippable frames are used to embed metadata into a stream of LZ4 frames.
+/// They are ignored by the decompression methods, except for
+/// [`decompress_with_skippable`], which passes them to a callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SkippableFrame<'a> {
+    /// The lowest 4 bits of the magic number, which can be used
+    /// to distinguish between different kinds of skippable frames.
+    pub index: u8,
+    /// The data inside the frame.
+    pub data: &'a [u8],
+}
+
+impl<'a> SkippableFrame<'a> {
+    /// Reads the skippable frame at the start of `input`, and returns it
+    /// together with the input that follows it.
+    ///
+    /// Returns `None` if `input` doesn't start with a skippable frame.
+    pub(crate) fn parse(input: &'a [u8]) -> Result<Option<(Self, &'a [u8])>, FrameError> {
+        let eof = FrameError::new(DecompressError::UnexpectedEof, input.len() as u64, None);
+        let field = |pos: usize| {
+            input
+                .get(pos..pos + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
 
-        // `0` is the end marker and indicates the end of the stream of blocks.
-        if size == 0 {
-            break;
+        match field(0) {
+            Some(magic) if super::is_skippable(magic) => {}
+            _ => return Ok(None),
+        }
+        let len = field(4).ok_or(eof)? as usize;
+        let rest = &input[8..];
+        if rest.len() < len {
+            return Err(eof);
         }
 
-        let is_uncompressed = size & UNCOMPRESSED_DATA != 0;
-        let size = size & !UNCOMPRESSED_DATA;
+        let frame = Self {
+            index: input[0] & 0xF,
+            data: &rest[..len],
+        };
+        Ok(Some((frame, &rest[len..])))
+    }
+}
 
-        let mut hash = None;
-        let mut hash_slice = |slice: &[u8]| {
-            if !flags.contains(Flags::BlockChecksums) {
-                return;
-            }
+/// An iterator over the frames inside of a slice, which contains
+/// multiple frames back to back.
+///
+/// Such input is produced by `lz4 -m`, or by concatenating the output of
+/// multiple compressions. Every item is a single frame, that can be passed to
+/// [`decompress`] or [`decompress_with_dictionaries`]. The frames are found by
+/// reading the frame headers and the sizes of the blocks, so nothing is decompressed
+/// and the checksums aren't verified. Blocks that are larger than the block size
+/// of their frame are still rejected. [Skippable frames](SkippableFrame) are skipped.
+///
+/// If the input contains anything else than frames, an error is returned and
+/// the iteration stops. Use [`decompress_frames`] to decompress all frames at once.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{Buf, FrameIter, HeapBuf};
+///
+/// let mut input = HeapBuf::new();
+/// lz4::compress(b"hello ", &mut input).unwrap();
+/// lz4::compress(b"world", &mut input).unwrap();
+///
+/// let mut out = HeapBuf::new();
+/// for frame in FrameIter::new(input.as_slice()) {
+///     lz4::decompress(frame.unwrap(), &mut out).unwrap();
+/// }
+/// assert_eq!(out.as_slice(), b"hello world");
+/// ```
+#[derive(Clone, Debug)]
+pub struct FrameIter<'a> {
+    input: &'a [u8],
+    /// The number of bytes in front of `input`.
+    position: usize,
+}
 
-            let mut hasher = XxHash32::with_seed(0);
-            hasher.write(slice);
-            hash = Some(hasher.finish() as u32);
-        };
+impl<'a> FrameIter<'a> {
+    /// Creates a new iterator over the frames inside of `input`.
+    pub fn new(input: &'a [u8]) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<&'a [u8]>, FrameError> {
+        let (skipped, input) =
+            skip_skippable(self.input).map_err(|err| err.offset(self.position as u64))?;
+        self.position += skipped;
+        if input.is_empty() {
+            return Ok(None);
+        }
 
-        match size {
-            // if the highest bit is set, this is uncompressed data
-            size if is_uncompressed => {
-                let source = reader.take(size as usize)?;
-                hash_slice(source);
+        let len = frame_len(input).map_err(|err| err.offset(self.position as u64))?;
+        let (frame, rest) = input.split_at(len);
+        self.input = rest;
+        self.position += len;
+        Ok(Some(frame))
+    }
+}
 
-                if !out.extend(source) {
-                    return Err(DecompressError::MemoryLimitExceeded);
-                }
+impl<'a> Iterator for FrameIter<'a> {
+    type Item = Result<&'a [u8], FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.next_frame();
+        if frame.is_err() {
+            self.input = &[];
+        }
+        frame.transpose()
+    }
+}
+
+/// Returns the length of the frame at the start of `input`, by skipping over its blocks.
+fn frame_len(input: &[u8]) -> Result<usize, FrameError> {
+    let mut reader = ByteIter::new(input);
+    let mut block = None;
+    let mut walk = || {
+        let header = read_header(&mut reader)?;
+        loop {
+            let size = u32::from_le_bytes(reader.read()?);
+            // `0` is the end marker and indicates the end of the stream of blocks.
+            if size == 0 {
+                block = None;
+                break;
             }
-            // if block is larger by max block size, treat it as uncompressed data
-            size if size > max_block_size => {
-                let source = reader.take(size as usize)?;
-                hash_slice(source);
-                if !out.extend(source) {
-                    return Err(DecompressError::MemoryLimitExceeded);
-                }
+
+            block = Some(block.map_or(0, |idx| idx + 1));
+            let size = (size & !UNCOMPRESSED_DATA) as usize;
+            if size > header.block_size.size() {
+                return Err(DecompressError::BlockTooLarge);
             }
-            // compressed data
-            size => {
-                let block = reader.take(size as usize)?;
-                hash_slice(block);
-                super::raw::decompress_block(block, out)?;
+            reader.take(size)?;
+            if header.flags.contains(Flags::BlockChecksums) {
+                reader.take(4)?;
             }
-        };
+        }
 
-        if let Some(actual) = hash.take() {
-            assert!(flags.contains(Flags::BlockChecksums));
-            let expected = u32::from_le_bytes(reader.read()?);
-            if actual != expected {
-                return Err(DecompressError::BlockChecksumInvalid);
+        if header.flags.contains(Flags::ContentChecksum) {
+            reader.take(4)?;
+        }
+        Ok(())
+    };
+
+    match walk() {
+        Ok(()) => Ok(reader.pos()),
+        Err(err) => Err(FrameError::new(err, reader.pos() as u64, block)),
+    }
+}
+
+/// Options that control how a frame is decompressed.
+///
+/// By default, all checksums that are part of the frame are verified and there
+/// are no limits. Since a tiny frame can decompress into gigabytes of data,
+/// untrusted input should be decompressed with limits, which make the decompression
+/// fail with [`DecompressError::LimitExceeded`] as soon as a limit would be exceeded.
+///
+/// The options are used by [`decompress_with`], [`decompress_frames_with`],
+/// [`decompress_with_dictionaries_and_options`],
+/// [`decompress_legacy_with`](crate::decompress::decompress_legacy_with),
+/// the [`PushDecoder`] and the [`FrameDecoder`](crate::decompress::FrameDecoder).
+///
+/// # Example
+///
+/// ```
+/// use lz4::{BlockSize, DecompressOptions};
+///
+/// let options = DecompressOptions::new()
+///     .max_output(16 * 1024 * 1024)
+///     .max_block_size(BlockSize::Max1MB)
+///     .require_content_checksum(true);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecompressOptions {
+    max_output: Option<u64>,
+    max_content_size: Option<u64>,
+    max_block_size: Option<BlockSize>,
+    pub(crate) verify_block_checksums: bool,
+    pub(crate) verify_content_checksum: bool,
+    require_content_checksum: bool,
+}
+
+impl DecompressOptions {
+    /// Create the default options, which don't limit anything.
+    pub const fn new() -> Self {
+        Self {
+            max_output: None,
+            max_content_size: None,
+            max_block_size: None,
+            verify_block_checksums: true,
+            verify_content_checksum: true,
+            require_content_checksum: false,
+        }
+    }
+
+    /// Limits the number of bytes a frame can decompress into.
+    ///
+    /// The limit is checked before the output is written, so `out` never
+    /// receives more than `max` bytes of the frame. Methods that decompress
+    /// multiple frames, like [`decompress_frames_with`], apply the limit to
+    /// the output of all frames together.
+    pub const fn max_output(mut self, max: u64) -> Self {
+        self.max_output = Some(max);
+        self
+    }
+
+    /// Rejects frames, whose header contains a content size larger than `max`.
+    pub const fn max_content_size(mut self, max: u64) -> Self {
+        self.max_content_size = Some(max);
+        self
+    }
+
+    /// Rejects frames, whose header allows blocks larger than `max`.
+    ///
+    /// Blocks are always limited to the block size of their frame header, so every
+    /// block that is accepted decompresses into at most `max` bytes.
+    pub const fn max_block_size(mut self, max: BlockSize) -> Self {
+        self.max_block_size = Some(max);
+        self
+    }
+
+    /// Sets if the checksums of the blocks are verified, if the frame contains them.
+    ///
+    /// Skipping the verification saves hashing the compressed data,
+    /// but corrupted blocks might decompress into garbage.
+    pub const fn verify_block_checksums(mut self, enabled: bool) -> Self {
+        self.verify_block_checksums = enabled;
+        self
+    }
+
+    /// Sets if the checksum of the content is verified, if the frame contains it.
+    ///
+    /// Skipping the verification saves hashing the decompressed data,
+    /// but corrupted frames might decompress into garbage.
+    pub const fn verify_content_checksum(mut self, enabled: bool) -> Self {
+        self.verify_content_checksum = enabled;
+        self
+    }
+
+    /// Sets if frames without a content checksum are rejected with
+    /// [`DecompressError::ContentChecksumMissing`].
+    ///
+    /// The content checksum is still only verified if
+    /// [`verify_content_checksum`](Self::verify_content_checksum) is enabled.
+    pub const fn require_content_checksum(mut self, enabled: bool) -> Self {
+        self.require_content_checksum = enabled;
+        self
+    }
+
+    /// Verifies that the frame header stays within the limits.
+    pub(crate) fn check_header(&self, header: &FrameHeader) -> Result<(), DecompressError> {
+        if self.require_content_checksum && !header.flags.contains(Flags::ContentChecksum) {
+            return Err(DecompressError::ContentChecksumMissing);
+        }
+        if let (Some(size), Some(max)) = (header.content_size, self.max_content_size) {
+            if size > max {
+                return Err(DecompressError::LimitExceeded);
             }
         }
+        self.check_block_size(header.block_size.size())
     }
 
-    if flags.contains(Flags::ContentChecksum) {
-        let mut hasher = XxHash32::with_seed(0);
-        hasher.write(out.as_slice());
-        let expected = hasher.finish() as u32;
+    /// Verifies that a frame may contain blocks of `size` bytes.
+    pub(crate) fn check_block_size(&self, size: usize) -> Result<(), DecompressError> {
+        match self.max_block_size {
+            Some(max) if size > max.size() => Err(DecompressError::LimitExceeded),
+            _ => Ok(()),
+        }
+    }
 
-        let actual = u32::from_le_bytes(reader.read()?);
-        if actual != expected {
-            return Err(DecompressError::ContentChecksumInvalid);
+    /// Returns how many bytes may be decompressed, after the previous frames
+    /// decompressed into `len` bytes.
+    pub(crate) fn remaining_output(&self, len: u64) -> u64 {
+        match self.max_output {
+            Some(max) => max.saturating_sub(len),
+            None => u64::MAX,
         }
     }
 
-    if let Some(expected) = content_size {
-        if expected as usize != out.len() {
-            return Err(DecompressError::ContentSizeInvalid);
+    /// Returns the options for a frame that follows frames, which
+    /// decompressed into `len` bytes.
+    pub(crate) fn after(mut self, len: u64) -> Self {
+        if self.max_output.is_some() {
+            self.max_output = Some(self.remaining_output(len));
         }
+        self
     }
 
+    /// Verifies that a frame may decompress into `len` bytes.
+    pub(crate) fn check_output(&self, len: u64) -> Result<(), DecompressError> {
+        match self.max_output {
+            Some(max) if len > max => Err(DecompressError::LimitExceeded),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for DecompressOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// This method can be used to decompress data that is compressed using
+/// the LZ4 [Frame Format].
+///
+/// The content of the frame is appended to `out`. Data that `out` already contains
+/// is left untouched and isn't part of the content checksum or the content size.
+///
+/// Frames that require a dictionary can't be decompressed with this method,
+/// use [`decompress_with_dictionaries`] for them.
+///
+/// Errors are returned as a [`FrameError`], which also contains the position of the error.
+/// Use [`FrameError::kind`] or the `?` operator to get the [`DecompressError`].
+///
+/// If you want a streaming decompresser, you have to enable `std` feature
+/// and use [`FrameDecoder`](crate::decompress::FrameDecoder), or use the
+/// [`PushDecoder`] which also works without `std`.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn decompress<B: Buf<u8>>(input: &[u8], out: &mut B) -> Result<(), FrameError> {
+    decompress_with_dictionaries(input, out, &())
+}
+
+/// Decompresses data that is compressed using the LZ4 [Frame Format],
+/// while using the given options.
+///
+/// See [`decompress`] and [`DecompressOptions`] for more information.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{Buf, DecompressError, DecompressOptions, HeapBuf};
+///
+/// let mut frame = HeapBuf::new();
+/// lz4::compress(&[0; 10_000], &mut frame).unwrap();
+///
+/// let options = DecompressOptions::new().max_output(1000);
+/// let mut out = HeapBuf::new();
+/// let err = lz4::decompress_with(frame.as_slice(), &mut out, &options).unwrap_err();
+/// assert_eq!(err.kind, DecompressError::LimitExceeded);
+/// assert!(out.len() <= 1000);
+/// ```
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn decompress_with<B: Buf<u8>>(
+    input: &[u8],
+    out: &mut B,
+    options: &DecompressOptions,
+) -> Result<(), FrameError> {
+    decompress_with_dictionaries_and_options(input, out, &(), options)
+}
+
+/// Decompresses all frames inside of `input`, which contains multiple
+/// LZ4 frames back to back.
+///
+/// The content of the frames is appended to `out`, and [skippable frames](SkippableFrame)
+/// are skipped, which matches the behaviour of the reference `lz4` tool.
+/// In contrast to [`decompress`], which stops after the first frame, an error is returned
+/// if `input` contains anything else after the frames. See [`FrameIter`] for more information.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn decompress_frames<B: Buf<u8>>(input: &[u8], out: &mut B) -> Result<(), FrameError> {
+    decompress_frames_with(input, out, &DecompressOptions::new())
+}
+
+/// Decompresses all frames inside of `input`, while using the given options.
+///
+/// The [output limit](DecompressOptions::max_output) applies to the content
+/// of all frames together. See [`decompress_frames`] for more information.
+pub fn decompress_frames_with<B: Buf<u8>>(
+    input: &[u8],
+    out: &mut B,
+    options: &DecompressOptions,
+) -> Result<(), FrameError> {
+    let len = out.len();
+    let mut frames = FrameIter::new(input);
+    while let Some(frame) = frames.next() {
+        let frame = frame?;
+        let start = frames.position - frame.len();
+        let options = options.after((out.len() - len) as u64);
+        decompress_with(frame, out, &options).map_err(|err| err.offset(start as u64))?;
+    }
     Ok(())
 }
+
+/// Decompresses data that is compressed using the LZ4 [Frame Format] and
+/// passes the [skippable frames](SkippableFrame) in front of it to `f`.
+///
+/// See [`decompress`] for more information.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{Buf, HeapBuf};
+///
+/// let mut input = HeapBuf::new();
+/// lz4::write_skippable_frame(0, b"metadata", &mut input).unwrap();
+/// lz4::compress(b"hello", &mut input).unwrap();
+///
+/// let mut out = HeapBuf::new();
+/// let mut metadata = Vec::new();
+/// lz4::decompress_with_skippable(input.as_slice(), &mut out, |frame| {
+///     metadata.extend_from_slice(frame.data)
+/// })
+/// .unwrap();
+/// assert_eq!(metadata, b"metadata");
+/// assert_eq!(out.as_slice(), b"hello");
+/// ```
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn decompress_with_skippable<B: Buf<u8>, F: FnMut(SkippableFrame<'_>)>(
+    mut input: &[u8],
+    out: &mut B,
+    mut f: F,
+) -> Result<(), FrameError> {
+    let mut skipped = 0;
+    while let Some((frame, rest)) =
+        SkippableFrame::parse(input).map_err(|err| err.offset(skipped as u64))?
+    {
+        f(frame);
+        skipped += input.len() - rest.len();
+        input = rest;
+    }
+    decompress(input, out).map_err(|err| err.offset(skipped as u64))
+}
+
+/// Decompresses data that is compressed using the LZ4 [Frame Format] and
+/// might reference a dictionary.
+///
+/// If the frame header contains a dictionary ID, the dictionary is looked up
+/// in `dicts` and [`DecompressError::UnknownDictionary`] is returned if it doesn't exist.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn decompress_with_dictionaries<B: Buf<u8>, D: Dictionaries + ?Sized>(
+    input: &[u8],
+    out: &mut B,
+    dicts: &D,
+) -> Result<(), FrameError> {
+    decompress_with_dictionaries_and_options(input, out, dicts, &DecompressOptions::new())
+}
+
+/// Decompresses data that is compressed using the LZ4 [Frame Format] and
+/// might reference a dictionary, while using the given options.
+///
+/// See [`decompress_with_dictionaries`] and [`DecompressOptions`] for more information.
+///
+/// [Frame Format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+pub fn decompress_with_dictionaries_and_options<B: Buf<u8>, D: Dictionaries + ?Sized>(
+    input: &[u8],
+    out: &mut B,
+    dicts: &D,
+    options: &DecompressOptions,
+) -> Result<(), FrameError> {
+    PushDecoder::with_dictionaries(dicts)
+        .with_options(*options)
+        .feed_all(input, out)
+}
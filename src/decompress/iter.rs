@@ -13,12 +13,21 @@ impl<'input> ByteIter<'input> {
         Self { bytes, idx: 0 }
     }
 
+    /// Returns the number of bytes that were read so far.
+    pub(crate) fn pos(&self) -> usize {
+        self.idx
+    }
+
     pub(crate) fn take(&mut self, count: usize) -> Result<&[u8], DecompressError> {
+        let end = self
+            .idx
+            .checked_add(count)
+            .ok_or(DecompressError::UnexpectedEof)?;
         let bytes = self
             .bytes
-            .get(self.idx..self.idx + count)
+            .get(self.idx..end)
             .ok_or(DecompressError::UnexpectedEof)?;
-        self.idx += count;
+        self.idx = end;
         Ok(bytes)
     }
 
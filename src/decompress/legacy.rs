@@ -0,0 +1,228 @@
+//! Decompression of the legacy LZ4 frame format.
+//!
+//! The legacy format is still produced by `lz4 -l` and used by the Linux kernel.
+//! It consists of the magic number, followed by blocks which are prefixed
+//! with their compressed size. There are no checksums and every block, except
+//! for the last one, contains 8MB of uncompressed data.
+
+use super::{decompress_block_ext, DecompressError, DecompressOptions, FrameError};
+use crate::Buf;
+
+/// The magic number at the start of a frame in the legacy format.
+pub(crate) const LEGACY_MAGIC: u32 = 0x184C2102;
+
+/// The amount of uncompressed data inside every block of a legacy frame.
+pub(crate) const LEGACY_BLOCK_SIZE: usize = 8 * 1024 * 1024;
+
+/// The largest compressed size of a block, anything larger is the
+/// magic number of the frame that follows.
+const MAX_COMPRESSED_SIZE: usize = match crate::compressed_bound(LEGACY_BLOCK_SIZE) {
+    Some(size) => size,
+    None => LEGACY_BLOCK_SIZE,
+};
+
+/// Decompresses data that is compressed using the legacy LZ4 frame format,
+/// which starts with the magic number `0x184C2102`.
+///
+/// The frame doesn't store where it ends, so the blocks are read until the end of
+/// `input` or until a value is found that can't be the size of a block, which is
+/// the magic number of the next frame. Legacy frames that directly follow each other
+/// are decompressed as a single one, everything else after the frame is ignored.
+///
+/// A block that decompresses into more than 8MB is rejected with
+/// [`DecompressError::BlockTooLarge`], before more than 8MB of it are written to `out`.
+/// If a block is invalid, the [position](FrameError::position) of the error
+/// points at the start of the block.
+pub fn decompress_legacy<B: Buf<u8>>(input: &[u8], out: &mut B) -> Result<(), FrameError> {
+    decompress_legacy_with(input, out, &DecompressOptions::new())
+}
+
+/// Decompresses data that is compressed using the legacy LZ4 frame format,
+/// while using the given options.
+///
+/// The [output limit](DecompressOptions::max_output) applies to all blocks together.
+/// Since every block of a legacy frame can contain 8MB, limiting the
+/// [block size](DecompressOptions::max_block_size) rejects all legacy frames.
+/// See [`decompress_legacy`] for more information.
+pub fn decompress_legacy_with<B: Buf<u8>>(
+    input: &[u8],
+    out: &mut B,
+    options: &DecompressOptions,
+) -> Result<(), FrameError> {
+    let mut rest = input;
+    let mut block = None;
+    let result = decompress_blocks(&mut rest, out, options, &mut block);
+    result.map_err(|err| FrameError::new(err, (input.len() - rest.len()) as u64, block))
+}
+
+/// Decompresses the blocks of the frame and removes them from `input`, while
+/// `block` is set to the index of the current block.
+fn decompress_blocks<B: Buf<u8>>(
+    input: &mut &[u8],
+    out: &mut B,
+    options: &DecompressOptions,
+    block: &mut Option<u64>,
+) -> Result<(), DecompressError> {
+    if read_u32(input)? != LEGACY_MAGIC {
+        return Err(DecompressError::InvalidMagic);
+    }
+    options.check_block_size(LEGACY_BLOCK_SIZE)?;
+    let len = out.len();
+
+    while !input.is_empty() {
+        let size = read_u32(input)?;
+        if size == LEGACY_MAGIC {
+            continue;
+        }
+
+        let size = size as usize;
+        if size > MAX_COMPRESSED_SIZE {
+            break;
+        }
+        if input.len() < size {
+            return Err(DecompressError::UnexpectedEof);
+        }
+        *block = Some(block.map_or(0, |idx| idx + 1));
+
+        // every block is independent of the previous ones
+        let start = out.len();
+        let remaining = options.remaining_output((start - len) as u64);
+        let limit = remaining.min(LEGACY_BLOCK_SIZE as u64) as usize;
+        match decompress_block_ext(&input[..size], &[], out, start, limit) {
+            // the output limit was reached before the end of the block
+            Err(DecompressError::BlockTooLarge) if limit < LEGACY_BLOCK_SIZE => {
+                return Err(DecompressError::LimitExceeded)
+            }
+            result => result?,
+        }
+        *input = &input[size..];
+    }
+
+    *block = None;
+    Ok(())
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, DecompressError> {
+    let bytes: &[u8] = input;
+    match bytes {
+        [a, b, c, d, rest @ ..] => {
+            *input = rest;
+            Ok(u32::from_le_bytes([*a, *b, *c, *d]))
+        }
+        _ => Err(DecompressError::UnexpectedEof),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Buf, DecompressError, DecompressOptions, HeapBuf};
+
+    /// `hello ` compressed by `lz4 -l`.
+    const HELLO: &[u8] = &[
+        0x02, 0x21, 0x4c, 0x18, 0x07, 0x00, 0x00, 0x00, 0x60, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20,
+    ];
+
+    #[test]
+    fn legacy_hello() {
+        let mut out = HeapBuf::new();
+        super::decompress_legacy(HELLO, &mut out).unwrap();
+        assert_eq!(out.as_slice(), b"hello ");
+
+        // concatenated frames are decompressed as a single one
+        let mut out = HeapBuf::new();
+        super::decompress_legacy(&[HELLO, HELLO].concat(), &mut out).unwrap();
+        assert_eq!(out.as_slice(), b"hello hello ");
+
+        // only the magic number, which is the output for empty input
+        let mut out = HeapBuf::new();
+        super::decompress_legacy(&HELLO[..4], &mut out).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn legacy_following_frame() {
+        let mut input = HELLO.to_vec();
+        let mut frame = HeapBuf::new();
+        crate::compress(b"world", &mut frame).unwrap();
+        input.extend_from_slice(frame.as_slice());
+
+        let mut out = HeapBuf::new();
+        super::decompress_legacy(&input, &mut out).unwrap();
+        assert_eq!(out.as_slice(), b"hello ");
+    }
+
+    #[test]
+    fn legacy_invalid() {
+        let mut out = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_legacy(&HELLO[1..], &mut out).map_err(|err| err.kind),
+            Err(DecompressError::InvalidMagic)
+        ));
+
+        for len in 5..HELLO.len() {
+            let mut out = HeapBuf::new();
+            assert!(matches!(
+                super::decompress_legacy(&HELLO[..len], &mut out).map_err(|err| err.kind),
+                Err(DecompressError::UnexpectedEof)
+            ));
+        }
+    }
+
+    #[test]
+    fn legacy_error_context() {
+        let mut input = HELLO.to_vec();
+        // a second block, whose match reaches into the first one
+        input.extend_from_slice(&[0x03, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00]);
+
+        let mut out = HeapBuf::new();
+        let err = super::decompress_legacy(&input, &mut out).unwrap_err();
+        assert_eq!(err.kind, DecompressError::OffsetOutOfBounds);
+        assert_eq!(err.block, Some(1));
+        assert_eq!(err.position, HELLO.len() as u64 + 4);
+    }
+
+    #[test]
+    fn legacy_block_too_large() {
+        // a block with a single match, that is 1MB longer than a block
+        let len = super::LEGACY_BLOCK_SIZE + 1024 * 1024 - 4 - 15;
+        let mut block = vec![0x1F, b'a', 1, 0];
+        block.resize(block.len() + len / 255, 255);
+        block.push((len % 255) as u8);
+        block.extend_from_slice(&[0x10, b'a']);
+
+        let mut input = super::LEGACY_MAGIC.to_le_bytes().to_vec();
+        input.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        input.extend_from_slice(&block);
+
+        let mut out = HeapBuf::new();
+        let err = super::decompress_legacy(&input, &mut out).unwrap_err();
+        assert_eq!(err.kind, DecompressError::BlockTooLarge);
+        assert_eq!(err.block, Some(0));
+        assert!(out.len() <= super::LEGACY_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn legacy_limits() {
+        let mut input = HeapBuf::new();
+        crate::compress_legacy(&[b'x'; 10_000], &mut input).unwrap();
+        crate::compress_legacy(&[b'y'; 10_000], &mut input).unwrap();
+
+        // the limit applies to all blocks
+        let options = DecompressOptions::new().max_output(15_000);
+        let mut out = HeapBuf::new();
+        let err = super::decompress_legacy_with(input.as_slice(), &mut out, &options).unwrap_err();
+        assert_eq!(err.kind, DecompressError::LimitExceeded);
+        assert_eq!(err.block, Some(1));
+        assert!(out.len() <= 15_000);
+
+        let options = DecompressOptions::new().max_output(20_000);
+        let mut out = HeapBuf::new();
+        super::decompress_legacy_with(input.as_slice(), &mut out, &options).unwrap();
+        assert_eq!(out.len(), 20_000);
+
+        // legacy blocks are always 8MB large
+        let options = DecompressOptions::new().max_block_size(crate::BlockSize::Max4MB);
+        let err = super::decompress_legacy_with(input.as_slice(), &mut out, &options).unwrap_err();
+        assert_eq!(err.kind, DecompressError::LimitExceeded);
+    }
+}
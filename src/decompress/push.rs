@@ -0,0 +1,1144 @@
+//! Push-based decompression of the LZ4 frame format, which doesn't depend
+//! on `std::io` and doesn't need the whole frame at once.
+
+use super::framed::{DecompressOptions, Dictionaries, FrameHeader, UNCOMPRESSED_DATA};
+use super::{ByteIter, DecompressError, Flags, FrameError};
+use crate::Buf;
+use core::hash::Hasher;
+use twox_hash::XxHash32;
+
+/// The longest field that has to be collected before it can be parsed,
+/// which is the frame descriptor with a content size and a dictionary ID.
+const MAX_FIELD_LEN: usize = 3 + 8 + 4;
+
+/// The maximum offset of a match is 65535, so this is the amount of
+/// previous output the [`RingWindow`] has to keep around.
+const WINDOW_SIZE: usize = 64 * 1024;
+
+/// The result of a single [`PushDecoder::feed`] call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of input bytes that were consumed.
+    ///
+    /// This is less than the length of the input if the frame is done,
+    /// or if the output buffer of [`PushDecoder::feed_windowed`] is full.
+    pub consumed: usize,
+    /// `true` if the end of the frame was reached and all checksums were verified.
+    pub done: bool,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Magic,
+    Header,
+    SkippableSize,
+    /// Inside the data of a skippable frame with `remaining` bytes left.
+    Skippable {
+        remaining: usize,
+    },
+    BlockSize,
+    /// Inside an uncompressed block with `remaining` bytes left.
+    Uncompressed {
+        remaining: usize,
+    },
+    /// Inside a compressed block with `remaining` bytes left.
+    Compressed {
+        remaining: usize,
+        seq: Sequence,
+    },
+    BlockChecksum,
+    ContentChecksum,
+    Done,
+}
+
+/// The part of a sequence, that is decoded next.
+#[derive(Clone, Copy)]
+enum Sequence {
+    Token,
+    /// Reading the additional bytes of the literal length.
+    LiteralLen {
+        token: u8,
+        len: usize,
+    },
+    Literals {
+        token: u8,
+        remaining: usize,
+    },
+    Offset {
+        token: u8,
+    },
+    /// Reading the additional bytes of the match length.
+    MatchLen {
+        offset: u16,
+        len: usize,
+    },
+    /// Copying a match with `remaining` bytes left.
+    Match {
+        offset: u16,
+        remaining: usize,
+    },
+}
+
+/// Keeps the last 64KB of decompressed data in a ring buffer, which is
+/// everything that matches of linked blocks are able to reference.
+///
+/// This is used by [`PushDecoder::feed_windowed`], to decompress frames
+/// of any block size using a constant amount of memory.
+#[derive(Clone)]
+pub struct RingWindow {
+    buf: [u8; WINDOW_SIZE],
+    /// The position where the next byte is written.
+    pos: usize,
+    /// The number of bytes that can be referenced.
+    filled: usize,
+}
+
+impl RingWindow {
+    /// Creates a new, empty window.
+    pub fn new() -> Self {
+        Self {
+            buf: [0; WINDOW_SIZE],
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.buf[self.pos] = byte;
+        self.pos = (self.pos + 1) % WINDOW_SIZE;
+        self.filled = WINDOW_SIZE.min(self.filled + 1);
+    }
+
+    fn extend(&mut self, data: &[u8]) {
+        // only the end of large slices stays inside the window
+        let data = &data[data.len().saturating_sub(WINDOW_SIZE)..];
+        let first = data.len().min(WINDOW_SIZE - self.pos);
+        self.buf[self.pos..self.pos + first].copy_from_slice(&data[..first]);
+        self.buf[..data.len() - first].copy_from_slice(&data[first..]);
+        self.pos = (self.pos + data.len()) % WINDOW_SIZE;
+        self.filled = WINDOW_SIZE.min(self.filled + data.len());
+    }
+
+    /// Returns the byte `offset` bytes in front of the end of the window,
+    /// which continues at the end of `dict`.
+    fn get(&self, offset: u16, dict: &[u8]) -> Result<u8, DecompressError> {
+        let offset = offset as usize;
+        if offset == 0 {
+            Err(DecompressError::ZeroMatchOffset)
+        } else if offset <= self.filled {
+            Ok(self.buf[(self.pos + WINDOW_SIZE - offset) % WINDOW_SIZE])
+        } else {
+            let back = offset - self.filled;
+            match dict.len().checked_sub(back) {
+                Some(idx) => Ok(dict[idx]),
+                None => Err(DecompressError::OffsetOutOfBounds),
+            }
+        }
+    }
+}
+
+impl Default for RingWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The destination of the decompressed data.
+trait Sink {
+    /// Returns the output buffer.
+    fn output(&self) -> &[u8];
+
+    /// Forgets the previous output, so it can't be referenced by matches anymore.
+    fn reset(&mut self);
+
+    /// Appends as many bytes of `data` as possible and returns their number.
+    fn literals(&mut self, data: &[u8]) -> Result<usize, DecompressError>;
+
+    /// Copies up to `len` bytes of a match and returns their number.
+    ///
+    /// Matches can reference the output starting at `start`, and `dict` in front of it.
+    fn copy(
+        &mut self,
+        offset: u16,
+        len: usize,
+        dict: &[u8],
+        start: usize,
+    ) -> Result<usize, DecompressError>;
+}
+
+/// Uses the output buffer itself to resolve matches.
+struct Flat<'out, B>(&'out mut B);
+
+impl<B: Buf<u8>> Sink for Flat<'_, B> {
+    fn output(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    fn reset(&mut self) {}
+
+    fn literals(&mut self, data: &[u8]) -> Result<usize, DecompressError> {
+        if self.0.extend(data) {
+            Ok(data.len())
+        } else {
+            Err(DecompressError::MemoryLimitExceeded)
+        }
+    }
+
+    fn copy(
+        &mut self,
+        offset: u16,
+        len: usize,
+        dict: &[u8],
+        start: usize,
+    ) -> Result<usize, DecompressError> {
+        super::raw::copy(offset as usize, len, self.0, dict, start)?;
+        Ok(len)
+    }
+}
+
+/// Resolves matches using a [`RingWindow`], so the output buffer
+/// can be emptied at any time.
+struct Ring<'out, B> {
+    window: &'out mut RingWindow,
+    out: &'out mut B,
+}
+
+impl<B: Buf<u8>> Sink for Ring<'_, B> {
+    fn output(&self) -> &[u8] {
+        self.out.as_slice()
+    }
+
+    fn reset(&mut self) {
+        self.window.filled = 0;
+    }
+
+    fn literals(&mut self, data: &[u8]) -> Result<usize, DecompressError> {
+        if self.out.extend(data) {
+            self.window.extend(data);
+            return Ok(data.len());
+        }
+
+        // fill the rest of the output
+        for (idx, &byte) in data.iter().enumerate() {
+            if self.out.push(byte).is_some() {
+                return Ok(idx);
+            }
+            self.window.push(byte);
+        }
+        Ok(data.len())
+    }
+
+    fn copy(
+        &mut self,
+        offset: u16,
+        len: usize,
+        dict: &[u8],
+        _: usize,
+    ) -> Result<usize, DecompressError> {
+        for idx in 0..len {
+            let byte = self.window.get(offset, dict)?;
+            if self.out.push(byte).is_some() {
+                return Ok(idx);
+            }
+            self.window.push(byte);
+        }
+        Ok(len)
+    }
+}
+
+/// Decompresses a single LZ4 frame, whose data is pushed into the decoder
+/// in chunks of any size.
+///
+/// This is useful if neither the whole frame can be kept in memory, nor `std::io`
+/// is available. Every byte that is passed to [`feed`](PushDecoder::feed) is processed
+/// immediately, so the frame can be split at any position and the decoder never buffers
+/// more than a few bytes of input. The decompressed data is appended to the `out` buffer.
+///
+/// Since matches reference the previously decompressed data, the same `out` buffer
+/// has to be passed to every call and must not be modified in between. To empty
+/// the output buffer between calls, use [`feed_windowed`](PushDecoder::feed_windowed).
+///
+/// [Skippable frames](crate::decompress::SkippableFrame) in front of the frame are skipped.
+///
+/// # Example
+///
+/// ```
+/// use lz4::{ArrayBuf, Buf, PushDecoder};
+///
+/// let frame = [
+///     0x04, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0x06, 0x00, 0x00, 0x80, 0x68, 0x65,
+///     0x6c, 0x6c, 0x6f, 0x0a, 0x00, 0x00, 0x00, 0x00, 0xf9, 0x5b, 0x6b, 0x94,
+/// ];
+///
+/// let mut decoder = PushDecoder::new();
+/// let mut out = ArrayBuf::<u8, 6>::new();
+/// for chunk in frame.chunks(4) {
+///     decoder.feed(chunk, &mut out).unwrap();
+/// }
+/// assert!(decoder.is_done());
+/// assert_eq!(out.as_slice(), b"hello\n");
+/// ```
+pub struct PushDecoder<'dicts, D: Dictionaries + ?Sized = ()> {
+    dicts: &'dicts D,
+    options: DecompressOptions,
+    state: State,
+    /// Collects fixed size fields, that are split across multiple chunks.
+    field: [u8; MAX_FIELD_LEN],
+    filled: usize,
+    flags: Flags,
+    max_block_size: usize,
+    content_size: Option<u64>,
+    dict: &'dicts [u8],
+    /// The position in the output, where the data that can be referenced by matches starts.
+    start: usize,
+    block_hasher: XxHash32,
+    content_hasher: XxHash32,
+    content_len: u64,
+    /// The number of bytes the current block has decompressed to so far.
+    block_len: usize,
+    /// The number of blocks that were started.
+    blocks: u64,
+    /// The number of input bytes that were consumed.
+    position: u64,
+}
+
+impl PushDecoder<'static> {
+    /// Creates a new decoder, that can't decompress frames which require a dictionary.
+    pub fn new() -> Self {
+        Self::with_dictionaries(&())
+    }
+}
+
+impl Default for PushDecoder<'static> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'dicts, D: Dictionaries + ?Sized> PushDecoder<'dicts, D> {
+    /// Creates a new decoder, that looks up the dictionary of the frame in `dicts`.
+    ///
+    /// See [`decompress_with_dictionaries`](crate::decompress::decompress_with_dictionaries)
+    /// for more information.
+    pub fn with_dictionaries(dicts: &'dicts D) -> Self {
+        Self {
+            dicts,
+            options: DecompressOptions::new(),
+            state: State::Magic,
+            field: [0; MAX_FIELD_LEN],
+            filled: 0,
+            flags: Flags::empty(),
+            max_block_size: 0,
+            content_size: None,
+            dict: &[],
+            start: 0,
+            block_hasher: XxHash32::with_seed(0),
+            content_hasher: XxHash32::with_seed(0),
+            content_len: 0,
+            block_len: 0,
+            blocks: 0,
+            position: 0,
+        }
+    }
+
+    /// Sets the options, which limit the frames this decoder accepts.
+    ///
+    /// See [`DecompressOptions`] for more information.
+    pub fn with_options(mut self, options: DecompressOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns `true` if the end of the frame was reached.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Decompresses the next chunk of the frame and appends the output to `out`.
+    ///
+    /// The whole `input` is consumed, unless the end of the frame is reached,
+    /// so the data that follows the frame can be handled by the caller.
+    /// After an error is returned, the decoder must not be used anymore.
+    /// The [position](FrameError::position) of the error counts all bytes that were passed
+    /// to the decoder, including the ones of previous calls.
+    pub fn feed<B: Buf<u8>>(&mut self, input: &[u8], out: &mut B) -> Result<Progress, FrameError> {
+        self.feed_to(input, &mut Flat(out))
+    }
+
+    /// Decompresses the next chunk of the frame and appends the output to `out`,
+    /// while matches are resolved using the `window`.
+    ///
+    /// In contrast to [`feed`](Self::feed), `out` only receives the new output
+    /// and can be emptied between calls, which allows to decompress frames of any size
+    /// using a fixed amount of memory. If `out` is full, the decoder stops and reports
+    /// how much input was consumed, so the caller can empty `out` and pass the rest
+    /// of the input again. The same `window` has to be passed to every call.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use lz4::{ArrayBuf, Buf, HeapBuf, PushDecoder, RingWindow};
+    ///
+    /// let mut frame = HeapBuf::new();
+    /// lz4::compress(&[b'x'; 100_000], &mut frame).unwrap();
+    ///
+    /// let mut decoder = PushDecoder::new();
+    /// let mut window = RingWindow::new();
+    /// let mut out = ArrayBuf::<u8, 512>::new();
+    /// let mut input = frame.as_slice();
+    /// let mut len = 0;
+    /// while !decoder.is_done() {
+    ///     let progress = decoder.feed_windowed(input, &mut window, &mut out).unwrap();
+    ///     input = &input[progress.consumed..];
+    ///     len += out.len();
+    ///     out.truncate(0);
+    /// }
+    /// assert_eq!(len, 100_000);
+    /// ```
+    pub fn feed_windowed<B: Buf<u8>>(
+        &mut self,
+        input: &[u8],
+        window: &mut RingWindow,
+        out: &mut B,
+    ) -> Result<Progress, FrameError> {
+        self.feed_to(input, &mut Ring { window, out })
+    }
+
+    /// Decompresses a frame, that must be completely contained in `input`.
+    pub(crate) fn feed_all<B: Buf<u8>>(
+        &mut self,
+        input: &[u8],
+        out: &mut B,
+    ) -> Result<(), FrameError> {
+        if self.feed(input, out)?.done {
+            Ok(())
+        } else {
+            Err(self.error(DecompressError::UnexpectedEof))
+        }
+    }
+
+    /// Adds the current position and block to the error.
+    pub(crate) fn error(&self, kind: DecompressError) -> FrameError {
+        FrameError::new(kind, self.position, self.block())
+    }
+
+    fn feed_to<S: Sink>(&mut self, input: &[u8], sink: &mut S) -> Result<Progress, FrameError> {
+        let mut rest = input;
+        let result = self.step(&mut rest, sink);
+        let consumed = input.len() - rest.len();
+        self.position += consumed as u64;
+
+        match result {
+            Ok(()) => Ok(Progress {
+                consumed,
+                done: self.is_done(),
+            }),
+            Err(kind) => Err(self.error(kind)),
+        }
+    }
+
+    /// Processes as much of `input` as possible and removes the processed bytes from it.
+    fn step<S: Sink>(&mut self, input: &mut &[u8], sink: &mut S) -> Result<(), DecompressError> {
+        loop {
+            match self.state {
+                State::Magic => match self.read_field::<4>(input) {
+                    Some(magic) if u32::from_le_bytes(magic) == super::MAGIC => {
+                        self.state = State::Header
+                    }
+                    Some(magic) if super::is_skippable(u32::from_le_bytes(magic)) => {
+                        self.state = State::SkippableSize
+                    }
+                    Some(_) => return Err(DecompressError::InvalidMagic),
+                    None => break,
+                },
+                State::Header => {
+                    // the first byte contains the flags, which decide about the length
+                    if !self.fill(input, 1) {
+                        break;
+                    }
+                    let header_len = FrameHeader::len(self.field[0]);
+                    if !self.fill(input, header_len) {
+                        break;
+                    }
+                    self.filled = 0;
+
+                    let header = FrameHeader::parse(&mut ByteIter::new(&self.field[..header_len]))?;
+                    self.options.check_header(&header)?;
+                    let dicts = self.dicts;
+                    self.dict = match header.dict_id {
+                        Some(id) => dicts.get(id).ok_or(DecompressError::UnknownDictionary)?,
+                        None => &[],
+                    };
+                    self.flags = header.flags;
+                    self.max_block_size = header.block_size.size();
+                    self.content_size = header.content_size;
+
+                    // the first block can only reference the dictionary
+                    self.start = sink.output().len();
+                    sink.reset();
+                    self.state = State::BlockSize;
+                }
+                State::SkippableSize => match self.read_field::<4>(input) {
+                    Some(size) => {
+                        self.state = State::Skippable {
+                            remaining: u32::from_le_bytes(size) as usize,
+                        }
+                    }
+                    None => break,
+                },
+                State::Skippable { remaining } => {
+                    let count = remaining.min(input.len());
+                    *input = &input[count..];
+                    if count < remaining {
+                        self.state = State::Skippable {
+                            remaining: remaining - count,
+                        };
+                        break;
+                    }
+                    // the actual frame follows the skippable one
+                    self.state = State::Magic;
+                }
+                State::BlockSize => {
+                    let size = match self.read_field::<4>(input) {
+                        Some(size) => u32::from_le_bytes(size),
+                        None => break,
+                    };
+
+                    // `0` is the end marker and indicates the end of the stream of blocks.
+                    if size == 0 {
+                        if self.flags.contains(Flags::ContentChecksum) {
+                            self.state = State::ContentChecksum;
+                        } else {
+                            self.finish()?;
+                        }
+                        continue;
+                    }
+
+                    let is_uncompressed = size & UNCOMPRESSED_DATA != 0;
+                    let remaining = (size & !UNCOMPRESSED_DATA) as usize;
+                    if remaining > self.max_block_size {
+                        return Err(DecompressError::BlockTooLarge);
+                    }
+
+                    // independent blocks can only reference the dictionary,
+                    // while linked blocks can also reference previous blocks
+                    if self.flags.contains(Flags::IndependentBlocks) {
+                        self.start = sink.output().len();
+                        sink.reset();
+                    }
+                    self.block_hasher = XxHash32::with_seed(0);
+                    self.block_len = 0;
+                    self.blocks += 1;
+
+                    self.state = if is_uncompressed {
+                        State::Uncompressed { remaining }
+                    } else {
+                        State::Compressed {
+                            remaining,
+                            seq: Sequence::Token,
+                        }
+                    };
+                }
+                State::Uncompressed { remaining } => {
+                    let count = remaining.min(input.len());
+                    self.check_output(count)?;
+                    let mark = sink.output().len();
+                    let used = sink.literals(&input[..count])?;
+                    self.consume(input, used, sink, mark);
+
+                    if used == remaining {
+                        self.end_block();
+                    } else {
+                        self.state = State::Uncompressed {
+                            remaining: remaining - used,
+                        };
+                        break;
+                    }
+                }
+                State::Compressed { remaining, seq } => {
+                    let count = remaining.min(input.len());
+                    let mark = sink.output().len();
+                    let mut data = &input[..count];
+                    let result = self.decode(seq, &mut data, sink);
+                    let used = count - data.len();
+                    self.consume(input, used, sink, mark);
+                    let seq = result?;
+
+                    if used == remaining {
+                        // a block always ends with the literals of the last sequence
+                        if !matches!(seq, Sequence::Offset { .. }) || self.filled != 0 {
+                            return Err(DecompressError::UnexpectedEof);
+                        }
+                        self.end_block();
+                    } else {
+                        self.state = State::Compressed {
+                            remaining: remaining - used,
+                            seq,
+                        };
+                        break;
+                    }
+                }
+                State::BlockChecksum => match self.read_field::<4>(input) {
+                    Some(checksum) => {
+                        let expected = u32::from_le_bytes(checksum);
+                        let actual = self.block_hasher.finish() as u32;
+                        if self.options.verify_block_checksums && expected != actual {
+                            return Err(DecompressError::BlockChecksumInvalid { expected, actual });
+                        }
+                        self.state = State::BlockSize;
+                    }
+                    None => break,
+                },
+                State::ContentChecksum => match self.read_field::<4>(input) {
+                    Some(checksum) => {
+                        let expected = u32::from_le_bytes(checksum);
+                        let actual = self.content_hasher.finish() as u32;
+                        if self.options.verify_content_checksum && expected != actual {
+                            return Err(DecompressError::ContentChecksumInvalid {
+                                expected,
+                                actual,
+                            });
+                        }
+                        self.finish()?;
+                    }
+                    None => break,
+                },
+                State::Done => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies bytes from `input` into the field buffer, until it contains `len` bytes.
+    ///
+    /// Returns `true` if enough bytes were collected.
+    fn fill(&mut self, input: &mut &[u8], len: usize) -> bool {
+        let count = len.saturating_sub(self.filled).min(input.len());
+        self.field[self.filled..self.filled + count].copy_from_slice(&input[..count]);
+        self.filled += count;
+        *input = &input[count..];
+        self.filled >= len
+    }
+
+    /// Reads a field of `N` bytes, which might have been started by a previous chunk.
+    fn read_field<const N: usize>(&mut self, input: &mut &[u8]) -> Option<[u8; N]> {
+        if !self.fill(input, N) {
+            return None;
+        }
+        self.filled = 0;
+
+        let mut field = [0; N];
+        field.copy_from_slice(&self.field[..N]);
+        Some(field)
+    }
+
+    /// Removes the `used` bytes of block data from `input` and adds the output,
+    /// that was produced since `mark`, to the content checksum.
+    fn consume<S: Sink>(&mut self, input: &mut &[u8], used: usize, sink: &S, mark: usize) {
+        if self.options.verify_block_checksums {
+            self.block_hasher.write(&input[..used]);
+        }
+        *input = &input[used..];
+
+        let output = &sink.output()[mark..];
+        if self.options.verify_content_checksum {
+            self.content_hasher.write(output);
+        }
+        self.content_len += output.len() as u64;
+        self.block_len += output.len();
+    }
+
+    /// Decodes the sequences inside `data`, which continue at the given part of a sequence.
+    ///
+    /// The used bytes are removed from `data`, which keeps some bytes only if the output
+    /// is full or an error occurred. Returns the part of the sequence, that must be decoded next.
+    fn decode<S: Sink>(
+        &mut self,
+        mut seq: Sequence,
+        data: &mut &[u8],
+        sink: &mut S,
+    ) -> Result<Sequence, DecompressError> {
+        let mark = sink.output().len();
+
+        loop {
+            seq = match seq {
+                // matches don't need any input
+                Sequence::Match { offset, remaining } => {
+                    self.check_output(sink.output().len() - mark + remaining)?;
+                    let copied = sink.copy(offset, remaining, self.dict, self.start)?;
+                    if copied < remaining {
+                        let seq = Sequence::Match {
+                            offset,
+                            remaining: remaining - copied,
+                        };
+                        return Ok(seq);
+                    }
+                    Sequence::Token
+                }
+                _ if data.is_empty() => break,
+                Sequence::Token => {
+                    let token = data[0];
+                    *data = &data[1..];
+                    match token >> 4 {
+                        15 => Sequence::LiteralLen { token, len: 15 },
+                        len => literals(token, len as usize),
+                    }
+                }
+                Sequence::LiteralLen { token, len } => {
+                    let byte = data[0];
+                    *data = &data[1..];
+                    match byte {
+                        255 => Sequence::LiteralLen {
+                            token,
+                            len: len + 255,
+                        },
+                        byte => literals(token, len + byte as usize),
+                    }
+                }
+                Sequence::Literals { token, remaining } => {
+                    let count = remaining.min(data.len());
+                    self.check_output(sink.output().len() - mark + count)?;
+                    let copied = sink.literals(&data[..count])?;
+                    *data = &data[copied..];
+                    if copied < count {
+                        let seq = Sequence::Literals {
+                            token,
+                            remaining: remaining - copied,
+                        };
+                        return Ok(seq);
+                    }
+                    literals(token, remaining - copied)
+                }
+                Sequence::Offset { token } => match self.read_field::<2>(data) {
+                    Some(offset) => {
+                        let offset = u16::from_le_bytes(offset);
+                        match token & 0xF {
+                            15 => Sequence::MatchLen { offset, len: 15 },
+                            len => Sequence::Match {
+                                offset,
+                                remaining: 4 + len as usize,
+                            },
+                        }
+                    }
+                    None => Sequence::Offset { token },
+                },
+                Sequence::MatchLen { offset, len } => {
+                    let byte = data[0];
+                    *data = &data[1..];
+                    match byte {
+                        255 => Sequence::MatchLen {
+                            offset,
+                            len: len + 255,
+                        },
+                        byte => Sequence::Match {
+                            offset,
+                            remaining: 4 + len + byte as usize,
+                        },
+                    }
+                }
+            };
+        }
+
+        Ok(seq)
+    }
+
+    /// Verifies that `len` more bytes of output, in addition to the output
+    /// of the previous steps, stay within the block size and the limits.
+    fn check_output(&self, len: usize) -> Result<(), DecompressError> {
+        if self.block_len.saturating_add(len) > self.max_block_size {
+            return Err(DecompressError::BlockTooLarge);
+        }
+        self.options
+            .check_output(self.content_len.saturating_add(len as u64))
+    }
+
+    /// Returns the index of the current block, if the decoder is inside of a block.
+    fn block(&self) -> Option<u64> {
+        match self.state {
+            State::Uncompressed { .. } | State::Compressed { .. } | State::BlockChecksum => {
+                self.blocks.checked_sub(1)
+            }
+            _ => None,
+        }
+    }
+
+    fn end_block(&mut self) {
+        self.state = if self.flags.contains(Flags::BlockChecksums) {
+            State::BlockChecksum
+        } else {
+            State::BlockSize
+        };
+    }
+
+    /// Verifies the content size and marks the frame as done.
+    fn finish(&mut self) -> Result<(), DecompressError> {
+        if let Some(expected) = self.content_size {
+            if expected != self.content_len {
+                return Err(DecompressError::ContentSizeInvalid {
+                    expected,
+                    actual: self.content_len,
+                });
+            }
+        }
+
+        self.state = State::Done;
+        Ok(())
+    }
+}
+
+/// Returns the part of the sequence that follows the literal length.
+fn literals(token: u8, remaining: usize) -> Sequence {
+    if remaining == 0 {
+        Sequence::Offset { token }
+    } else {
+        Sequence::Literals { token, remaining }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PushDecoder, RingWindow};
+    use crate::{
+        compress_with, compress_with_dict, ArrayBuf, BlockSize, Buf, DecompressError,
+        DecompressOptions, FrameError, FramePreferences, HeapBuf,
+    };
+
+    fn compress(input: &[u8], prefs: &FramePreferences) -> HeapBuf<u8> {
+        let mut compressed = HeapBuf::new();
+        compress_with(input, &mut compressed, prefs).unwrap();
+        compressed
+    }
+
+    /// Feeds the frame in chunks of `size` bytes.
+    fn decompress_chunked(frame: &[u8], size: usize) -> Result<HeapBuf<u8>, FrameError> {
+        let mut decoder = PushDecoder::new();
+        let mut out = HeapBuf::new();
+        for chunk in frame.chunks(size) {
+            let progress = decoder.feed(chunk, &mut out)?;
+            assert_eq!(progress.consumed, chunk.len());
+        }
+        assert!(decoder.is_done());
+        Ok(out)
+    }
+
+    #[test]
+    fn push_every_split() {
+        let input = b"The quick brown fox jumps over the lazy dog. ".repeat(30);
+        let prefs = FramePreferences::new()
+            .block_checksums(true)
+            .content_size(true);
+        let frame = compress(&input, &prefs);
+        let frame = frame.as_slice();
+
+        for split in 0..=frame.len() {
+            let mut decoder = PushDecoder::new();
+            let mut out = HeapBuf::new();
+            let first = decoder.feed(&frame[..split], &mut out).unwrap();
+            assert_eq!(first.consumed, split);
+            assert_eq!(first.done, split == frame.len());
+            decoder.feed(&frame[split..], &mut out).unwrap();
+            assert!(decoder.is_done());
+            assert!(out.as_slice() == &input[..]);
+        }
+    }
+
+    #[test]
+    fn push_chunks() {
+        let input = crate::test_data(400 * 1024, 1, &[100, 3000, 20_000]);
+        for &independent_blocks in &[true, false] {
+            let prefs = FramePreferences::new().independent_blocks(independent_blocks);
+            let frame = compress(&input, &prefs);
+            for &size in &[1, 3, 1000, 65536, 70000] {
+                let out = decompress_chunked(frame.as_slice(), size).unwrap();
+                assert!(out.as_slice() == &input[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn push_trailing_data() {
+        let mut frame = compress(b"hello", &FramePreferences::new());
+        let len = frame.len();
+        frame.extend(b"trailing");
+
+        let mut decoder = PushDecoder::new();
+        let mut out = HeapBuf::new();
+        let progress = decoder.feed(frame.as_slice(), &mut out).unwrap();
+        assert_eq!(progress.consumed, len);
+        assert!(progress.done);
+
+        // the decoder doesn't consume anything after the end of the frame
+        assert_eq!(decoder.feed(b"more", &mut out).unwrap().consumed, 0);
+        assert_eq!(out.as_slice(), b"hello");
+    }
+
+    #[test]
+    fn push_skippable() {
+        let mut input = HeapBuf::new();
+        crate::write_skippable_frame(3, b"metadata", &mut input).unwrap();
+        crate::write_skippable_frame(15, &[], &mut input).unwrap();
+        let frame = compress(b"hello", &FramePreferences::new());
+        input.extend(frame.as_slice());
+
+        for &size in &[1, 5, 100] {
+            let out = decompress_chunked(input.as_slice(), size).unwrap();
+            assert_eq!(out.as_slice(), b"hello");
+        }
+    }
+
+    #[test]
+    fn push_limits() {
+        let prefs = FramePreferences::new()
+            .block_size(BlockSize::Max4MB)
+            .content_size(true);
+        let frame = compress(&vec![0; 8 * 1024 * 1024], &prefs);
+        let frame = frame.as_slice();
+
+        let feed = |options: DecompressOptions, out: &mut HeapBuf<u8>| {
+            PushDecoder::new().with_options(options).feed(frame, out)
+        };
+
+        // the output never grows beyond the limit
+        let mut out = HeapBuf::new();
+        let options = DecompressOptions::new().max_output(1024 * 1024);
+        assert!(matches!(
+            feed(options, &mut out).map_err(|err| err.kind),
+            Err(DecompressError::LimitExceeded)
+        ));
+        assert!(out.len() <= 1024 * 1024);
+
+        let mut window = RingWindow::new();
+        let mut out = ArrayBuf::<u8, 100>::new();
+        let mut decoder = PushDecoder::new().with_options(options);
+        let mut input = frame;
+        let mut len = 0;
+        let err = loop {
+            match decoder.feed_windowed(input, &mut window, &mut out) {
+                Ok(progress) => input = &input[progress.consumed..],
+                Err(err) => break err,
+            }
+            len += out.len();
+            out.truncate(0);
+        };
+        assert_eq!(err.kind, DecompressError::LimitExceeded);
+        assert!(len <= 1024 * 1024);
+
+        // the limits of the header are checked before decompressing anything
+        for &options in &[
+            DecompressOptions::new().max_content_size(8 * 1024 * 1024 - 1),
+            DecompressOptions::new().max_block_size(BlockSize::Max1MB),
+        ] {
+            let mut out = HeapBuf::new();
+            assert!(matches!(
+                feed(options, &mut out).map_err(|err| err.kind),
+                Err(DecompressError::LimitExceeded)
+            ));
+            assert!(out.is_empty());
+        }
+
+        let options = DecompressOptions::new()
+            .max_output(8 * 1024 * 1024)
+            .max_content_size(8 * 1024 * 1024)
+            .max_block_size(BlockSize::Max4MB);
+        assert!(feed(options, &mut HeapBuf::new()).unwrap().done);
+    }
+
+    #[test]
+    fn push_block_too_large() {
+        let prefs = FramePreferences::new().block_size(BlockSize::Max64KB);
+        let header = compress(&[], &prefs).as_slice()[..7].to_vec();
+
+        // the compressed size of a block is larger than the block size
+        let mut frame = header.clone();
+        frame.extend_from_slice(&70_000u32.to_le_bytes());
+        frame.extend_from_slice(&[0; 70_000]);
+        let mut out = HeapBuf::new();
+        let err = PushDecoder::new().feed(&frame, &mut out).unwrap_err();
+        assert_eq!(err.kind, DecompressError::BlockTooLarge);
+        assert_eq!(err.position, 11);
+        assert!(out.is_empty());
+
+        // a small block, that decompresses to 100,004 bytes
+        let mut block = vec![0x1F, b'a', 1, 0];
+        let len = 100_000 - 1 - 4 - 15;
+        block.resize(block.len() + len / 255, 255);
+        block.push((len % 255) as u8);
+        block.extend_from_slice(&[0x40, b'a', b'a', b'a', b'a']);
+        let mut frame = header;
+        frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&block);
+
+        for &options in &[
+            DecompressOptions::new(),
+            DecompressOptions::new().max_block_size(BlockSize::Max64KB),
+        ] {
+            let mut out = HeapBuf::new();
+            let mut decoder = PushDecoder::new().with_options(options);
+            let err = decoder.feed(&frame, &mut out).unwrap_err();
+            assert_eq!(err.kind, DecompressError::BlockTooLarge);
+            assert_eq!(err.block, Some(0));
+            assert!(out.len() <= 64 * 1024);
+
+            let mut window = RingWindow::new();
+            let mut out = ArrayBuf::<u8, 1000>::new();
+            let mut decoder = PushDecoder::new().with_options(options);
+            let mut input = &frame[..];
+            let mut len = 0;
+            let err = loop {
+                match decoder.feed_windowed(input, &mut window, &mut out) {
+                    Ok(progress) => input = &input[progress.consumed..],
+                    Err(err) => break err,
+                }
+                len += out.len();
+                out.truncate(0);
+            };
+            assert_eq!(err.kind, DecompressError::BlockTooLarge);
+            assert!(len <= 64 * 1024);
+        }
+
+        // the same block is fine with a larger block size
+        let mut frame = compress(
+            &[],
+            &FramePreferences::new().block_size(BlockSize::Max256KB),
+        )
+        .as_slice()[..7]
+            .to_vec();
+        frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&block);
+        frame.extend_from_slice(&[0; 4]);
+        let mut out = HeapBuf::new();
+        let mut decoder = PushDecoder::new()
+            .with_options(DecompressOptions::new().verify_content_checksum(false));
+        decoder.feed(&frame, &mut out).unwrap();
+        assert_eq!(out.len(), 100_004);
+    }
+
+    #[test]
+    fn push_checksum_options() {
+        let input = &crate::test_data(8000, 1, &[100, 3000])[..];
+        let prefs = FramePreferences::new().block_checksums(true);
+        let frame = compress(input, &prefs);
+        let decode = |frame: &[u8], options: DecompressOptions| {
+            let mut out = HeapBuf::new();
+            PushDecoder::new()
+                .with_options(options)
+                .feed(frame, &mut out)
+                .map(|_| out)
+        };
+
+        // corrupt the block checksum of the first block and the content checksum
+        let mut corrupted = frame.as_slice().to_vec();
+        let block_size =
+            u32::from_le_bytes([corrupted[7], corrupted[8], corrupted[9], corrupted[10]]);
+        corrupted[11 + (block_size & 0x7FFF_FFFF) as usize] ^= 1;
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 1;
+
+        assert!(matches!(
+            decode(&corrupted, DecompressOptions::new()).map_err(|err| err.kind),
+            Err(DecompressError::BlockChecksumInvalid { .. })
+        ));
+        assert!(matches!(
+            decode(
+                &corrupted,
+                DecompressOptions::new().verify_block_checksums(false)
+            )
+            .map_err(|err| err.kind),
+            Err(DecompressError::ContentChecksumInvalid { .. })
+        ));
+        let options = DecompressOptions::new()
+            .verify_block_checksums(false)
+            .verify_content_checksum(false);
+        assert!(decode(&corrupted, options).unwrap().as_slice() == input);
+
+        // strict mode only accepts frames with a content checksum
+        let strict = DecompressOptions::new().require_content_checksum(true);
+        assert!(decode(frame.as_slice(), strict).is_ok());
+        let frame = compress(input, &prefs.content_checksum(false));
+        assert!(matches!(
+            decode(frame.as_slice(), strict).map_err(|err| err.kind),
+            Err(DecompressError::ContentChecksumMissing)
+        ));
+    }
+
+    #[test]
+    fn push_invalid() {
+        let input = &crate::test_data(8000, 1, &[100, 3000])[..];
+        let frame = compress(input, &FramePreferences::new());
+        let frame = frame.as_slice();
+
+        let mut corrupted = frame.to_vec();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 1;
+        assert!(matches!(
+            decompress_chunked(&corrupted, 7).map_err(|err| err.kind),
+            Err(DecompressError::ContentChecksumInvalid { .. })
+        ));
+
+        assert!(matches!(
+            decompress_chunked(&frame[1..], 7).map_err(|err| err.kind),
+            Err(DecompressError::InvalidMagic)
+        ));
+    }
+
+    /// Decompresses the frame in chunks of `size` bytes into an output buffer of `N` bytes.
+    fn decompress_windowed<const N: usize>(
+        frame: &[u8],
+        size: usize,
+        dicts: &[(u32, &[u8])],
+    ) -> Vec<u8> {
+        let mut decoder = PushDecoder::with_dictionaries(dicts);
+        let mut window = RingWindow::new();
+        let mut out = ArrayBuf::<u8, N>::new();
+        let mut decompressed = Vec::new();
+
+        for mut chunk in frame.chunks(size) {
+            loop {
+                let progress = decoder.feed_windowed(chunk, &mut window, &mut out).unwrap();
+                chunk = &chunk[progress.consumed..];
+                let produced = out.len();
+                decompressed.extend_from_slice(out.as_slice());
+                out.truncate(0);
+
+                // continue until the whole chunk is consumed and the output isn't full anymore
+                if chunk.is_empty() && produced < N {
+                    break;
+                }
+            }
+        }
+        assert!(decoder.is_done());
+        decompressed
+    }
+
+    #[test]
+    fn push_windowed() {
+        let input = crate::test_data(800 * 1024, 2, &[100, 3000, 20_000]);
+        for &independent_blocks in &[true, false] {
+            let prefs = FramePreferences::new()
+                .block_size(BlockSize::Max1MB)
+                .independent_blocks(independent_blocks)
+                .content_size(true);
+            let frame = compress(&input, &prefs);
+            let frame = frame.as_slice();
+
+            assert!(decompress_windowed::<1>(frame, 100_000, &[]) == input);
+            assert!(decompress_windowed::<1000>(frame, 777, &[]) == input);
+            assert!(decompress_windowed::<70_000>(frame, 1, &[]) == input);
+        }
+    }
+
+    #[test]
+    fn push_windowed_dictionary() {
+        let data = crate::test_data(72 * 1024, 3, &[100, 3000]);
+        let (dict, input) = data.split_at(8 * 1024);
+        let prefs = FramePreferences::new()
+            .independent_blocks(false)
+            .dictionary_id(7);
+
+        let mut frame = HeapBuf::new();
+        compress_with_dict(input, dict, &mut frame, &prefs).unwrap();
+        let decompressed = decompress_windowed::<300>(frame.as_slice(), 1000, &[(7, dict)]);
+        assert!(decompressed == input);
+    }
+}
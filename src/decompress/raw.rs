@@ -3,6 +3,9 @@
 use super::{ByteIter, DecompressError};
 use crate::Buf;
 
+/// The maximum number of bytes from a dictionary that can be referenced by a block.
+const MAX_DICT_SIZE: usize = 64 * 1024;
+
 /// Decompresses a LZ4-compressed block of `data`
 ///
 /// The decompressed data will be written into the `out` buffer. If the buffer
@@ -13,7 +16,37 @@ use crate::Buf;
 /// is compressed using the frame format. For decompressing data like this use
 /// [`decompress`](crate::decompress::decompress) function instead.
 pub fn decompress_block<O: Buf<u8>>(data: &[u8], out: &mut O) -> Result<(), DecompressError> {
+    decompress_block_ext(data, &[], out, 0, usize::MAX)
+}
+
+/// Decompresses a LZ4-compressed block of `data`, that was compressed using a dictionary.
+///
+/// This works like [`decompress_block`], but matches that reach before the start of `out`
+/// are read from the end of `dict`. Since the offset of a match is at most 64KB,
+/// only the last 64KB of the dictionary are ever used.
+pub fn decompress_block_with_dict<O: Buf<u8>>(
+    data: &[u8],
+    dict: &[u8],
+    out: &mut O,
+) -> Result<(), DecompressError> {
+    let dict = &dict[dict.len().saturating_sub(MAX_DICT_SIZE)..];
+    decompress_block_ext(data, dict, out, 0, usize::MAX)
+}
+
+/// Decompresses a LZ4-compressed block of `data`, whose matches can
+/// reference `out[start..]` and the `dict` in front of it.
+///
+/// If the block decompresses into more than `limit` bytes, [`DecompressError::BlockTooLarge`]
+/// is returned before `out` grows beyond the limit.
+pub(crate) fn decompress_block_ext<O: Buf<u8>>(
+    data: &[u8],
+    dict: &[u8],
+    out: &mut O,
+    start: usize,
+    limit: usize,
+) -> Result<(), DecompressError> {
     let mut reader = ByteIter::new(data);
+    let end = out.len().saturating_add(limit);
 
     // loop through all sequences
     while let Ok(token) = reader.read_byte() {
@@ -25,11 +58,13 @@ pub fn decompress_block<O: Buf<u8>>(data: &[u8], out: &mut O) -> Result<(), Deco
         let len = reader.read_int((token >> 4) as usize)?;
 
         // now copy `len` literal bytes into the output
-        if !out.reserve(len) {
+        let slice = reader.take(len)?;
+        if end - out.len() < len {
+            return Err(DecompressError::BlockTooLarge);
+        }
+        if !out.extend(slice) {
             return Err(DecompressError::MemoryLimitExceeded);
         }
-        let slice = reader.take(len)?;
-        out.extend(slice);
 
         // read low byte of the next offset
         let low = match reader.read_byte() {
@@ -47,9 +82,12 @@ pub fn decompress_block<O: Buf<u8>>(data: &[u8], out: &mut O) -> Result<(), Deco
         //
         // the minimum value of the len is 4, which leads to 19 as the maxium value
         let len = 4 + reader.read_int((token & 0xF) as usize)?;
+        if end - out.len() < len {
+            return Err(DecompressError::BlockTooLarge);
+        }
 
         // now copy the data that is duplicated
-        copy(offset as usize, len, out)?;
+        copy(offset as usize, len, out, dict, start)?;
     }
 
     Ok(())
@@ -57,32 +95,60 @@ pub fn decompress_block<O: Buf<u8>>(data: &[u8], out: &mut O) -> Result<(), Deco
 
 // TODO: Probably replace with `ptr::copy`
 /// Optimized version of the copy operation.
-fn copy<O: Buf<u8>>(offset: usize, len: usize, out: &mut O) -> Result<(), DecompressError> {
-    let out_len = out.len();
+pub(super) fn copy<O: Buf<u8>>(
+    offset: usize,
+    mut len: usize,
+    out: &mut O,
+    dict: &[u8],
+    start: usize,
+) -> Result<(), DecompressError> {
+    if offset == 0 {
+        return Err(DecompressError::ZeroMatchOffset);
+    }
+    let produced = out
+        .len()
+        .checked_sub(start)
+        .ok_or(DecompressError::OffsetOutOfBounds)?;
+
+    // the match starts inside the dictionary
+    if offset > produced {
+        let back = offset - produced;
+        if back > dict.len() {
+            return Err(DecompressError::OffsetOutOfBounds);
+        }
+
+        let dict_start = dict.len() - back;
+        let count = back.min(len);
+        if !out.extend(&dict[dict_start..dict_start + count]) {
+            return Err(DecompressError::MemoryLimitExceeded);
+        }
+
+        // the rest of the match continues at the start of the output
+        len -= count;
+        if len == 0 {
+            return Ok(());
+        }
+    }
 
+    // at this point, the match starts inside the output,
+    // so there are at least `offset` bytes in front of it
+    let out_len = out.len();
+    let from = out_len - offset;
     match offset {
-        // invalid offset
-        0 => return Err(DecompressError::ZeroMatchOffset),
         // repeat the last byte we output
         1 => {
-            if !out.resize(
-                out_len + len,
-                out.as_slice()
-                    .last()
-                    .copied()
-                    .expect("output should ever be filled here"),
-            ) {
+            let byte = out.as_slice()[from];
+            if !out.resize(out_len + len, byte) {
                 return Err(DecompressError::MemoryLimitExceeded);
             }
         }
         // copy each byte manually
-        offset => {
+        _ => {
             if !out.reserve(len) {
                 return Err(DecompressError::MemoryLimitExceeded);
             }
-            let start = out_len - offset;
             (0..len).for_each(|idx| {
-                let x = out.as_slice()[start + idx];
+                let x = out.as_slice()[from + idx];
                 out.push(x);
             });
         }
@@ -93,7 +159,7 @@ fn copy<O: Buf<u8>>(offset: usize, len: usize, out: &mut O) -> Result<(), Decomp
 
 #[cfg(test)]
 mod tests {
-    use crate::{ArrayBuf, Buf};
+    use crate::{ArrayBuf, Buf, DecompressError, HeapBuf};
 
     fn decompress_block<'res, S: Buf<u8>>(buf: &'res mut S, input: &[u8]) -> &'res str {
         super::decompress_block(input, buf).unwrap();
@@ -124,4 +190,66 @@ mod tests {
             "The quick brown fox jumps over the lazy dog."
         );
     }
+
+    #[test]
+    fn block_offset_out_of_bounds() {
+        // offset 2, but only a single byte of output
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_block(&[0x10, b'a', 2, 0], &mut buf),
+            Err(DecompressError::OffsetOutOfBounds)
+        ));
+
+        // offset 1 without any output
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_block(&[0x00, 1, 0], &mut buf),
+            Err(DecompressError::OffsetOutOfBounds)
+        ));
+
+        // the dictionary is too short
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_block_with_dict(&[0x10, b'a', 4, 0], b"xy", &mut buf),
+            Err(DecompressError::OffsetOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn block_truncated_literals() {
+        let mut buf = HeapBuf::new();
+        assert!(matches!(
+            super::decompress_block(&[0xF0, 0xFF, 0xFF, 0x10, b'a'], &mut buf),
+            Err(DecompressError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn block_with_dict() {
+        let dict = b"hello world";
+        let raw = [0x07, 11, 0, 0x10, b'!'];
+        let mut buf = ArrayBuf::<u8, 12>::new();
+        super::decompress_block_with_dict(&raw, dict, &mut buf).unwrap();
+        assert_eq!(buf.as_slice(), b"hello world!");
+    }
+
+    #[test]
+    fn block_with_dict_overlapping() {
+        // the match starts inside the dictionary and continues in the output
+        let raw = [0x14, b'x', 5, 0];
+        let mut buf = ArrayBuf::<u8, 16>::new();
+        super::decompress_block_with_dict(&raw, b"abcd", &mut buf).unwrap();
+        assert_eq!(buf.as_slice(), b"xabcdxabc");
+    }
+
+    #[test]
+    fn block_with_large_dict() {
+        let mut dict = vec![0u8; 100_000];
+        dict[100_000 - 0xFFFF..][..4].copy_from_slice(b"abcd");
+
+        let raw = [0x00, 0xFF, 0xFF];
+        let mut buf = ArrayBuf::<u8, 4>::new();
+        super::decompress_block_with_dict(&raw, &dict, &mut buf).unwrap();
+        assert_eq!(buf.as_slice(), b"abcd");
+    }
 }
@@ -0,0 +1,255 @@
+//! Streaming decompression of the LZ4 frame format, using the `std::io` traits.
+
+use super::{DecompressError, DecompressOptions, FrameError, PushDecoder, RingWindow};
+use crate::Buf;
+use std::io::{self, Read};
+
+/// The number of bytes that are read from the inner reader at once.
+const INPUT_SIZE: usize = 8 * 1024;
+
+/// Decompresses a single LZ4 frame, that is read from `R`.
+///
+/// In contrast to [`decompress`](crate::decompress::decompress), the frame is decoded
+/// while it is read, using a [`PushDecoder`]. Only a small input buffer and the last 64KB
+/// of the decompressed data, which are required for linked blocks, are kept in memory.
+/// Since the input is buffered, the decoder may read past the end of the frame.
+///
+/// The content checksum and the content size are verified when reaching the end
+/// of the frame. Errors are returned as an [`io::Error`] of kind [`InvalidData`](io::ErrorKind::InvalidData),
+/// that wraps the [`FrameError`].
+///
+/// This struct is only available with the `std` feature enabled.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Read;
+///
+/// let frame = [
+///     0x04, 0x22, 0x4d, 0x18, 0x64, 0x40, 0xa7, 0x06, 0x00, 0x00, 0x80, 0x68, 0x65,
+///     0x6c, 0x6c, 0x6f, 0x0a, 0x00, 0x00, 0x00, 0x00, 0xf9, 0x5b, 0x6b, 0x94,
+/// ];
+///
+/// let mut decoder = lz4::FrameDecoder::new(&frame[..]);
+/// let mut decompressed = String::new();
+/// decoder.read_to_string(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, "hello\n");
+/// ```
+pub struct FrameDecoder<R> {
+    reader: R,
+    decoder: PushDecoder<'static>,
+    window: Box<RingWindow>,
+    input: Box<[u8; INPUT_SIZE]>,
+    /// The range of `input` that wasn't passed to the decoder yet.
+    pos: usize,
+    len: usize,
+}
+
+impl<R: Read> FrameDecoder<R> {
+    /// Creates a new decoder, that reads the compressed frame from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, DecompressOptions::new())
+    }
+
+    /// Creates a new decoder, that reads the compressed frame from `reader`
+    /// and uses the given options.
+    pub fn with_options(reader: R, options: DecompressOptions) -> Self {
+        Self {
+            reader,
+            decoder: PushDecoder::new().with_options(options),
+            window: Box::new(RingWindow::new()),
+            input: Box::new([0; INPUT_SIZE]),
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    ///
+    /// Reading from the underlying reader will corrupt the frame.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Consumes this decoder, returning the underlying reader.
+    ///
+    /// Data that was already read from the underlying reader, but not yet decompressed, is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for FrameDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.decoder.is_done() {
+                return Ok(0);
+            }
+
+            if self.pos == self.len {
+                self.len = self.reader.read(&mut self.input[..])?;
+                self.pos = 0;
+                if self.len == 0 {
+                    return Err(invalid(self.decoder.error(DecompressError::UnexpectedEof)));
+                }
+            }
+
+            let mut out = SliceBuf { slice: buf, len: 0 };
+            let input = &self.input[self.pos..self.len];
+            let progress = self
+                .decoder
+                .feed_windowed(input, &mut self.window, &mut out)
+                .map_err(invalid)?;
+            self.pos += progress.consumed;
+
+            if out.len > 0 {
+                return Ok(out.len);
+            }
+        }
+    }
+}
+
+/// A `Buf` that writes into the buffer of a `read` call.
+struct SliceBuf<'a> {
+    slice: &'a mut [u8],
+    len: usize,
+}
+
+impl Buf<u8> for SliceBuf<'_> {
+    fn push(&mut self, item: u8) -> Option<u8> {
+        match self.slice.get_mut(self.len) {
+            Some(entry) => {
+                *entry = item;
+                self.len += 1;
+                None
+            }
+            None => Some(item),
+        }
+    }
+
+    fn reserve(&mut self, count: usize) -> bool {
+        self.len + count <= self.slice.len()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.slice[..self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.slice[..self.len]
+    }
+
+    fn extend(&mut self, buf: &[u8]) -> bool {
+        if !self.reserve(buf.len()) {
+            return false;
+        }
+        self.slice[self.len..self.len + buf.len()].copy_from_slice(buf);
+        self.len += buf.len();
+        true
+    }
+
+    fn truncate(&mut self, len: usize) -> bool {
+        self.len = self.len.min(len);
+        true
+    }
+}
+
+fn invalid(err: FrameError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameDecoder;
+    use crate::{
+        compress_with, BlockSize, Buf, DecompressError, FrameError, FramePreferences, HeapBuf,
+    };
+    use std::io::{self, Read};
+
+    fn input() -> Vec<u8> {
+        crate::test_data(1200 * 1024, 1, &[100, 3000, 20_000])
+    }
+
+    fn compress(input: &[u8], prefs: FramePreferences) -> Vec<u8> {
+        let mut compressed = HeapBuf::new();
+        compress_with(input, &mut compressed, &prefs).unwrap();
+        compressed.as_slice().to_vec()
+    }
+
+    #[test]
+    fn stream_roundtrip() {
+        let input = input();
+        for &independent_blocks in &[true, false] {
+            let prefs = FramePreferences::default()
+                .independent_blocks(independent_blocks)
+                .block_checksums(true)
+                .content_size(true);
+            let compressed = compress(&input, prefs);
+
+            let mut decompressed = Vec::new();
+            FrameDecoder::new(&compressed[..])
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert!(decompressed == input);
+        }
+    }
+
+    #[test]
+    fn stream_small_reads() {
+        let input = input();
+        let prefs = FramePreferences::default()
+            .block_size(BlockSize::Max256KB)
+            .independent_blocks(false);
+        let compressed = compress(&input, prefs);
+
+        let mut decoder = FrameDecoder::new(&compressed[..]);
+        let mut decompressed = Vec::new();
+        let mut buf = [0; 1000];
+        loop {
+            match decoder.read(&mut buf).unwrap() {
+                0 => break,
+                len => decompressed.extend_from_slice(&buf[..len]),
+            }
+        }
+        assert!(decompressed == input);
+    }
+
+    #[test]
+    fn stream_invalid() {
+        let input = input();
+        let mut compressed = compress(&input, FramePreferences::default());
+
+        // truncated frame
+        let err = FrameDecoder::new(&compressed[..compressed.len() - 2])
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // corrupted content checksum
+        let last = compressed.len() - 1;
+        compressed[last] ^= 1;
+        let err = FrameDecoder::new(&compressed[..])
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = err.into_inner().unwrap().downcast::<FrameError>().unwrap();
+        assert!(matches!(
+            err.kind,
+            DecompressError::ContentChecksumInvalid { .. }
+        ));
+        assert_eq!(err.position, compressed.len() as u64);
+    }
+}
@@ -32,3 +32,34 @@ pub const fn compressed_bound(size: usize) -> Option<usize> {
         Some(size + (size / 255) + 16)
     }
 }
+
+/// Generates `len` bytes of data for the tests.
+///
+/// The data consists of chunks of 32 bytes, which are either random bytes or a copy
+/// of the data `distance` bytes in front of them, cycling through the `distances`.
+/// Without any `distances`, the data is incompressible.
+/// The same `seed` always results in the same data.
+#[cfg(test)]
+pub(crate) fn test_data(len: usize, seed: u32, distances: &[usize]) -> alloc::vec::Vec<u8> {
+    let mut state = seed | 1;
+    let mut data = alloc::vec::Vec::with_capacity(len);
+    let mut chunk = 0;
+
+    while data.len() < len {
+        let start = data.len();
+        let end = len.min(start + 32);
+        match distances.get(chunk) {
+            Some(&distance) if distance <= start => {
+                (start..end).for_each(|pos| data.push(data[pos - distance]));
+            }
+            _ => (start..end).for_each(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                data.push(state as u8);
+            }),
+        }
+        chunk = (chunk + 1) % (distances.len() + 1);
+    }
+    data
+}
@@ -0,0 +1,29 @@
+//! Helpers that are shared by the integration tests and the fuzz targets.
+
+use lz4::{ArrayBuf, Buf, DecompressError, PushDecoder, RingWindow};
+
+/// Decompresses a frame by feeding it to the push decoder in chunks of `size` bytes,
+/// while the output is collected using a small buffer.
+pub fn decompress_pushed(input: &[u8], size: usize) -> Result<Vec<u8>, DecompressError> {
+    let mut decoder = PushDecoder::new();
+    let mut window = RingWindow::new();
+    let mut out = ArrayBuf::<u8, 64>::new();
+    let mut decompressed = Vec::new();
+
+    for mut chunk in input.chunks(size) {
+        while !chunk.is_empty() && !decoder.is_done() {
+            let progress = decoder
+                .feed_windowed(chunk, &mut window, &mut out)
+                .map_err(|err| err.kind)?;
+            chunk = &chunk[progress.consumed..];
+            decompressed.extend_from_slice(out.as_slice());
+            out.truncate(0);
+        }
+    }
+
+    if decoder.is_done() {
+        Ok(decompressed)
+    } else {
+        Err(DecompressError::UnexpectedEof)
+    }
+}
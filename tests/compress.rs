@@ -0,0 +1,73 @@
+use lz4::{Buf, HeapBuf};
+use std::io;
+
+macro_rules! data_path {
+    ($name:literal) => {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/", $name)
+    };
+}
+
+fn test_block_roundtrip(path: &str) -> io::Result<()> {
+    let data = std::fs::read(path)?;
+
+    let mut compressed = HeapBuf::new();
+    lz4::compress_block(&data, &mut compressed).expect("failed to compress file");
+    assert!(compressed.len() <= lz4::compressed_bound(data.len()).unwrap());
+
+    let mut decompressed = HeapBuf::new();
+    lz4::decompress_block(compressed.as_slice(), &mut decompressed)
+        .expect("failed to decompress file");
+    assert!(
+        data == decompressed.as_slice(),
+        "{} doesn't match it's roundtripped data",
+        path
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_block_wallpaper() {
+    test_block_roundtrip(data_path!("wallpaper.jpg")).expect("I/O error");
+}
+
+fn test_frame_roundtrip(path: &str) -> io::Result<()> {
+    let data = std::fs::read(path)?;
+
+    let mut compressed = HeapBuf::new();
+    lz4::compress(&data, &mut compressed).expect("failed to compress file");
+
+    let mut decompressed = HeapBuf::new();
+    lz4::decompress(compressed.as_slice(), &mut decompressed).expect("failed to decompress file");
+    assert!(
+        data == decompressed.as_slice(),
+        "{} doesn't match it's roundtripped data",
+        path
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_frame_wallpaper() {
+    test_frame_roundtrip(data_path!("wallpaper.jpg")).expect("I/O error");
+}
+
+#[test]
+fn test_stream_wallpaper() {
+    let path = data_path!("wallpaper.jpg");
+    let data = std::fs::read(path).expect("I/O error");
+
+    let mut encoder = lz4::FrameEncoder::new(Vec::new());
+    io::copy(
+        &mut std::fs::File::open(path).expect("I/O error"),
+        &mut encoder,
+    )
+    .expect("failed to compress file");
+    encoder.finish().expect("failed to compress file");
+    let compressed = encoder.into_inner();
+
+    let mut decompressed = HeapBuf::new();
+    lz4::decompress(&compressed, &mut decompressed).expect("failed to decompress file");
+    assert!(data == decompressed.as_slice());
+}
@@ -1,7 +1,10 @@
-use lz4::{Buf, HeapBuf};
+use common::decompress_pushed;
+use lz4::{Buf, DecompressError, HeapBuf};
 use std::io;
 use twox_hash::xxh3::hash64;
 
+mod common;
+
 macro_rules! data_path {
     ($name:literal) => {
         concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/", $name)
@@ -47,3 +50,145 @@ fn test_64mb_zero() {
 fn test_64mb_random() {
     test_file_pair(data_path!("64_MB_compressed.bin"), data_path!("64_MB.bin")).expect("I/O error");
 }
+
+#[test]
+fn test_wallpaper_stream() {
+    let compressed =
+        std::fs::File::open(data_path!("wallpaper_compressed.jpg")).expect("I/O error");
+    let decompressed = std::fs::read(data_path!("wallpaper.jpg")).expect("I/O error");
+
+    let mut buf = Vec::new();
+    io::copy(&mut lz4::FrameDecoder::new(compressed), &mut buf).expect("failed to decompress file");
+    assert_eq!(hash64(&decompressed), hash64(&buf));
+}
+
+/// A small xorshift generator, so the malformed inputs are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Runs every decompression method on `input`, which only must not panic.
+fn decompress_all(input: &[u8]) {
+    let _ = lz4::decompress_block(input, &mut HeapBuf::new());
+    let _ = lz4::decompress_block(input, &mut lz4::ArrayBuf::<u8, 64>::new());
+    let _ = lz4::decompress_block_with_dict(input, b"dictionary", &mut HeapBuf::new());
+    let _ = lz4::decompress(input, &mut HeapBuf::new());
+    let _ = lz4::decompress_frames(input, &mut HeapBuf::new());
+    let _ = lz4::decompress_legacy(input, &mut HeapBuf::new());
+    let _ = lz4::frame_info(input);
+    let _ = io::copy(&mut lz4::FrameDecoder::new(input), &mut io::sink());
+
+    // the push decoder must agree with `decompress`, no matter how the input is split
+    let mut out = HeapBuf::new();
+    let expected = lz4::decompress(input, &mut out).map(|()| out.as_slice().to_vec());
+    assert_eq!(
+        expected.map_err(|err| err.kind),
+        decompress_pushed(input, 7)
+    );
+}
+
+#[test]
+fn test_malformed() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let data = b"The quick brown fox jumps over the lazy dog. ".repeat(40);
+
+    let mut samples = Vec::new();
+    let mut frame = HeapBuf::new();
+    lz4::compress(&data, &mut frame).unwrap();
+    samples.push(frame.as_slice().to_vec());
+    let prefs = lz4::FramePreferences::new()
+        .independent_blocks(false)
+        .block_checksums(true)
+        .content_size(true);
+    let mut frame = HeapBuf::new();
+    lz4::compress_with(&data, &mut frame, &prefs).unwrap();
+    samples.push(frame.as_slice().to_vec());
+    let mut block = HeapBuf::new();
+    lz4::compress_block(&data, &mut block).unwrap();
+    samples.push(block.as_slice().to_vec());
+    let mut legacy = HeapBuf::new();
+    lz4::compress_legacy(&data, &mut legacy).unwrap();
+    samples.push(legacy.as_slice().to_vec());
+
+    for sample in &samples {
+        for len in 0..sample.len() {
+            decompress_all(&sample[..len]);
+        }
+
+        for _ in 0..2000 {
+            let mut input = sample.clone();
+            for _ in 0..1 + rng.below(4) {
+                let idx = rng.below(input.len());
+                input[idx] = rng.next() as u8;
+            }
+            decompress_all(&input);
+        }
+    }
+
+    for _ in 0..2000 {
+        let len = rng.below(64);
+        let input = (0..len).map(|_| rng.next() as u8).collect::<Vec<_>>();
+        decompress_all(&input);
+    }
+}
+
+#[test]
+fn test_block_too_large() {
+    // a frame with blocks of at most 64KB
+    let prefs = lz4::FramePreferences::new().block_size(lz4::BlockSize::Max64KB);
+    let mut header = HeapBuf::new();
+    lz4::compress_with(&[], &mut header, &prefs).unwrap();
+    let header = &header.as_slice()[..7];
+
+    // a compressed block, that is larger than the block size
+    let mut large = header.to_vec();
+    large.extend_from_slice(&70_000u32.to_le_bytes());
+    large.extend_from_slice(&[0; 70_000]);
+    large.extend_from_slice(&[0; 8]);
+
+    // a small block, that decompresses to 100,004 bytes
+    let mut expanding = header.to_vec();
+    let mut block = vec![0x1F, b'a', 1, 0];
+    block.resize(block.len() + 99_980 / 255, 255);
+    block.push((99_980 % 255) as u8);
+    block.extend_from_slice(&[0x40, b'a', b'a', b'a', b'a']);
+    expanding.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    expanding.extend_from_slice(&block);
+    expanding.extend_from_slice(&[0; 8]);
+
+    for frame in &[&large, &expanding] {
+        let mut out = HeapBuf::new();
+        let err = lz4::decompress(frame, &mut out).unwrap_err();
+        assert_eq!(err.kind, DecompressError::BlockTooLarge);
+        assert!(out.len() <= 64 * 1024);
+
+        let err = lz4::decompress_frames(frame, &mut HeapBuf::new()).unwrap_err();
+        assert_eq!(err.kind, DecompressError::BlockTooLarge);
+        assert_eq!(
+            decompress_pushed(frame, 1000),
+            Err(DecompressError::BlockTooLarge)
+        );
+
+        let err = io::copy(&mut lz4::FrameDecoder::new(&frame[..]), &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // the iterator doesn't decompress the blocks, but their size is still checked
+    let mut frames = lz4::FrameIter::new(&large);
+    assert_eq!(
+        frames.next().unwrap().unwrap_err().kind,
+        DecompressError::BlockTooLarge
+    );
+    assert!(frames.next().is_none());
+}